@@ -1,34 +1,146 @@
 use chrono::NaiveDateTime;
 use tauri::AppHandle;
+use crate::commands::printing::{device, escpos, receipt};
+use crate::commands::printing::device::PrinterStatus;
 use crate::commands::database::{
     connection::establish_connection,
+    errors::CommandError,
     models::{
-        product::{Product, NewProduct},
-        customer::{Customer, NewCustomer},
+        product::{Product, NewProduct, NewProductBarcode, NewProductUnit, ProductBarcode, ProductUnit, ProductMatch, ProductPage, ProductSearch, ScannedProduct},
+        customer::{Customer, CustomerMatch, NewCustomer},
+        category::{Category, CategorySales, NewCategory},
+        bundle::{Bundle, NewBundleComponent},
+        variant::{NewProductTemplate, ProductTemplate, ProductVariant, TemplateSales},
         sale::{Sale, NewSale},
         sale_detail::{SaleDetail, NewSaleDetail, SaleDetailWithProduct},
         invoice::{Invoice, NewInvoice},
-        user::{User, NewUser},
+        user::{User, NewUser, UserRole, AuthResult},
+        two_factor::{TwoFactorEnrollment, TwoFactorStatus},
+        approval::{ApprovalRequest, SupervisorApproval},
+        settings::{BusinessSettings, TaxSettings, PasswordPolicy, TwoFactorPolicy, PosRestrictions, InventorySettings, PrinterSettings, ScaleBarcodeSettings, SetupStatus, SystemSetup},
+        audit::{AuditEntry, AuditLogFilter},
+        sale_chain::ChainVerification,
+        payment::{NewSalePayment, PaymentMethod, SaleCompletion, SalePayment, TenderTotal},
+        register::{CashRegister, NewCashRegister, RegisterSession, OpenRegisterSession, CloseRegisterSession, CashMovement, NewCashMovement},
+        report::{RegisterReport, ZReportSummary},
+        inventory::{InventoryMovement, MovementType, NewInventoryMovement},
+        sale_return::{SaleReturn, NewSaleReturn},
     },
     queries::{
         product_queries,
         customer_queries,
+        category_queries,
+        variant_queries,
+        bundle_queries,
         sale_queries,
         sale_detail_queries,
         invoice_queries,
-        user_queries
+        user_queries,
+        settings_queries,
+        two_factor_queries,
+        approval_queries,
+        audit_queries,
+        sale_chain_queries,
+        inventory_queries,
+        return_queries,
+        payment_queries,
+        register_queries,
+        report_queries,
+        receipt_queries
     }
 };
 
+fn find_user(conn: &rusqlite::Connection, user_id: i32) -> Result<User, String> {
+    user_queries::get_user_by_id(conn, user_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "User not found".to_string())
+}
+
+// Quien ejecuta una operación debe estar activo y no tener pendiente un
+// cambio de contraseña; solo `change_own_password` queda fuera de esta regla.
+fn find_actor(conn: &rusqlite::Connection, actor_id: i32) -> Result<User, String> {
+    let actor = find_pending_actor(conn, actor_id)?;
+    // Si su rol exige verificación en dos pasos, lo único que puede hacer es inscribirse
+    if two_factor_queries::is_required(conn, actor.role).map_err(|e| e.to_string())?
+        && !two_factor_queries::is_enabled(conn, actor.id).map_err(|e| e.to_string())?
+    {
+        return Err("Debe activar la verificación en dos pasos antes de continuar".to_string());
+    }
+    Ok(actor)
+}
+
+// Usuario activo y con la contraseña al día, aunque le falte inscribir el
+// segundo factor obligatorio. Solo para los comandos de inscripción.
+fn find_pending_actor(conn: &rusqlite::Connection, actor_id: i32) -> Result<User, String> {
+    let actor = find_user(conn, actor_id)?;
+    if !actor.is_active {
+        return Err("El usuario está desactivado".to_string());
+    }
+    if actor.must_change_password {
+        return Err("Debe cambiar su contraseña antes de continuar".to_string());
+    }
+    Ok(actor)
+}
+
+// Quien aún no se inscribe solo puede hacerlo sobre su propia cuenta
+fn find_enrolling_actor(conn: &rusqlite::Connection, actor_id: i32, user_id: i32) -> Result<User, String> {
+    if actor_id == user_id {
+        find_pending_actor(conn, actor_id)
+    } else {
+        find_actor(conn, actor_id)
+    }
+}
+
+// Cambios de seguridad sobre una cuenta: los hace el propio usuario tras
+// confirmar su contraseña, o un administrador.
+fn authorize_account_change(
+    conn: &rusqlite::Connection,
+    actor: &User,
+    user_id: i32,
+    password: Option<&str>,
+) -> Result<(), String> {
+    if actor.role == UserRole::Admin {
+        return Ok(());
+    }
+    if actor.id != user_id {
+        return Err("Solo el propio usuario o un administrador pueden modificar esta cuenta".to_string());
+    }
+    let password = password.ok_or_else(|| "Confirme su contraseña actual".to_string())?;
+    if !user_queries::verify_user_password(conn, user_id, password).map_err(|e| e.to_string())? {
+        return Err("La contraseña actual es incorrecta".to_string());
+    }
+    Ok(())
+}
+
+fn require_admin(conn: &rusqlite::Connection, actor_id: i32) -> Result<User, String> {
+    let actor = find_actor(conn, actor_id)?;
+    if actor.role != UserRole::Admin {
+        return Err("Solo un administrador puede realizar esta operación".to_string());
+    }
+    Ok(actor)
+}
+
 /* ========== PRODUCTOS ========== */
+// Las búsquedas mientras se escribe regresan a lo más 100 resultados
+fn search_limit(limit: Option<u32>) -> u32 {
+    limit.unwrap_or(20).clamp(1, 100)
+}
+
 #[tauri::command]
 pub async fn add_product(
     app_handle: AppHandle,
+    actor_id: i32,
     product: NewProduct,
 ) -> Result<i32, String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    product_queries::create_product(&conn, product)
-        .map_err(|e| e.to_string())
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let id = product_queries::create_product(&tx, actor_id, product).map_err(|e| e.to_string())?;
+    let after = product_queries::get_product(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "create", "product", Some(id), None, Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(id)
 }
 
 #[tauri::command]
@@ -51,21 +163,421 @@ pub async fn get_all_products(app_handle: AppHandle) -> Result<Vec<Product>, Str
 #[tauri::command]
 pub async fn update_product(
     app_handle: AppHandle,
+    actor_id: i32,
     id: i32,
     product: NewProduct,
 ) -> Result<(), String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    product_queries::update_product(&conn, id, product)
-        .map_err(|e| e.to_string())
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = product_queries::get_product(&tx, id).map_err(|e| e.to_string())?;
+    product_queries::update_product(&tx, id, product).map_err(|e| e.to_string())?;
+    let after = product_queries::get_product(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "update", "product", Some(id), Some(&before), Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub async fn delete_product(
     app_handle: AppHandle,
+    actor_id: i32,
+    id: i32,
+) -> Result<(), String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = product_queries::get_product(&tx, id).map_err(|e| e.to_string())?;
+    product_queries::delete_product(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "delete", "product", Some(id), Some(&before), None)
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn find_product_by_code(
+    app_handle: AppHandle,
+    code: String,
+) -> Result<Option<ScannedProduct>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    product_queries::find_product_by_code(&conn, &code)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn search_products(
+    app_handle: AppHandle,
+    search: ProductSearch,
+) -> Result<ProductPage, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    product_queries::search_products(&conn, &search)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn search_products_text(
+    app_handle: AppHandle,
+    text: String,
+    limit: Option<u32>,
+) -> Result<Vec<ProductMatch>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    product_queries::search_products_text(&conn, &text, search_limit(limit))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn add_product_barcode(
+    app_handle: AppHandle,
+    actor_id: i32,
+    barcode: NewProductBarcode,
+) -> Result<ProductBarcode, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let id = product_queries::add_barcode(&tx, &barcode).map_err(|e| e.to_string())?;
+    let after = product_queries::get_barcode(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "create", "product_barcode", Some(id), None, Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(after)
+}
+
+#[tauri::command]
+pub async fn get_product_barcodes(
+    app_handle: AppHandle,
+    product_id: i32,
+) -> Result<Vec<ProductBarcode>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    product_queries::get_barcodes(&conn, product_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_product_barcode(
+    app_handle: AppHandle,
+    actor_id: i32,
+    id: i32,
+) -> Result<(), String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = product_queries::get_barcode(&tx, id).map_err(|e| e.to_string())?;
+    product_queries::delete_barcode(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "delete", "product_barcode", Some(id), Some(&before), None)
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn add_product_unit(
+    app_handle: AppHandle,
+    actor_id: i32,
+    unit: NewProductUnit,
+) -> Result<ProductUnit, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let id = product_queries::add_unit(&tx, &unit).map_err(|e| e.to_string())?;
+    let after = product_queries::get_unit(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "create", "product_unit", Some(id), None, Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(after)
+}
+
+#[tauri::command]
+pub async fn get_product_units(
+    app_handle: AppHandle,
+    product_id: i32,
+) -> Result<Vec<ProductUnit>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    product_queries::get_units(&conn, product_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_product_unit(
+    app_handle: AppHandle,
+    actor_id: i32,
+    id: i32,
+) -> Result<(), String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = product_queries::get_unit(&tx, id).map_err(|e| e.to_string())?;
+    product_queries::delete_unit(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "delete", "product_unit", Some(id), Some(&before), None)
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/* ========== CATEGORÍAS ========== */
+#[tauri::command]
+pub async fn add_category(
+    app_handle: AppHandle,
+    actor_id: i32,
+    category: NewCategory,
+) -> Result<Category, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let id = category_queries::create_category(&tx, &category).map_err(|e| e.to_string())?;
+    let after = category_queries::get_category(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "create", "category", Some(id), None, Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(after)
+}
+
+#[tauri::command]
+pub async fn get_categories(app_handle: AppHandle) -> Result<Vec<Category>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    category_queries::get_categories(&conn)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn update_category(
+    app_handle: AppHandle,
+    actor_id: i32,
+    id: i32,
+    category: NewCategory,
+) -> Result<Category, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = category_queries::get_category(&tx, id).map_err(|e| e.to_string())?;
+    category_queries::update_category(&tx, id, &category).map_err(|e| e.to_string())?;
+    let after = category_queries::get_category(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "update", "category", Some(id), Some(&before), Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(after)
+}
+
+#[tauri::command]
+pub async fn delete_category(
+    app_handle: AppHandle,
+    actor_id: i32,
     id: i32,
 ) -> Result<(), String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    product_queries::delete_product(&conn, id)
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = category_queries::get_category(&tx, id).map_err(|e| e.to_string())?;
+    category_queries::delete_category(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "delete", "category", Some(id), Some(&before), None)
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/* ========== MODELOS Y VARIANTES ========== */
+#[tauri::command]
+pub async fn add_product_template(
+    app_handle: AppHandle,
+    actor_id: i32,
+    template: NewProductTemplate,
+) -> Result<ProductTemplate, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let id = variant_queries::create_template(&tx, &template).map_err(|e| e.to_string())?;
+    let after = variant_queries::get_template(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "create", "product_template", Some(id), None, Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(after)
+}
+
+#[tauri::command]
+pub async fn get_product_templates(app_handle: AppHandle) -> Result<Vec<ProductTemplate>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    variant_queries::get_templates(&conn)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn add_template_attribute_value(
+    app_handle: AppHandle,
+    actor_id: i32,
+    template_id: i32,
+    attribute_id: i32,
+    value: String,
+) -> Result<ProductTemplate, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = variant_queries::get_template(&tx, template_id).map_err(|e| e.to_string())?;
+    if !before.attributes.iter().any(|attribute| attribute.id == attribute_id) {
+        return Err(format!("El atributo {} no pertenece al modelo {}", attribute_id, template_id));
+    }
+    variant_queries::add_attribute_value(&tx, attribute_id, &value).map_err(|e| e.to_string())?;
+    let after = variant_queries::get_template(&tx, template_id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "update", "product_template", Some(template_id), Some(&before), Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(after)
+}
+
+#[tauri::command]
+pub async fn update_template_price(
+    app_handle: AppHandle,
+    actor_id: i32,
+    template_id: i32,
+    base_price: f64,
+) -> Result<ProductTemplate, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = variant_queries::get_template(&tx, template_id).map_err(|e| e.to_string())?;
+    variant_queries::set_template_price(&tx, template_id, base_price).map_err(|e| e.to_string())?;
+    let after = variant_queries::get_template(&tx, template_id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "update", "product_template", Some(template_id), Some(&before), Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(after)
+}
+
+#[tauri::command]
+pub async fn generate_product_variants(
+    app_handle: AppHandle,
+    actor_id: i32,
+    template_id: i32,
+) -> Result<Vec<ProductVariant>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let created = variant_queries::generate_variants(&tx, actor_id, template_id).map_err(|e| e.to_string())?;
+    let variants = created
+        .into_iter()
+        .map(|id| variant_queries::get_variant(&tx, id))
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(|e| e.to_string())?;
+    for variant in &variants {
+        audit_queries::record(&tx, Some(actor_id), "create", "product", Some(variant.product.id), None, Some(&variant.product))
+            .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(variants)
+}
+
+#[tauri::command]
+pub async fn get_product_variants(
+    app_handle: AppHandle,
+    template_id: i32,
+) -> Result<Vec<ProductVariant>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    variant_queries::get_variants(&conn, template_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_variant_price(
+    app_handle: AppHandle,
+    actor_id: i32,
+    product_id: i32,
+    price_override: Option<f64>,
+) -> Result<ProductVariant, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = variant_queries::get_variant(&tx, product_id).map_err(|e| e.to_string())?;
+    variant_queries::set_variant_price(&tx, product_id, price_override).map_err(|e| e.to_string())?;
+    let after = variant_queries::get_variant(&tx, product_id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "update", "product", Some(product_id), Some(&before), Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(after)
+}
+
+/* ========== PAQUETES ========== */
+#[tauri::command]
+pub async fn set_bundle_components(
+    app_handle: AppHandle,
+    actor_id: i32,
+    bundle_id: i32,
+    components: Vec<NewBundleComponent>,
+) -> Result<Option<Bundle>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = bundle_queries::find_bundle(&tx, bundle_id).map_err(|e| e.to_string())?;
+    bundle_queries::set_components(&tx, bundle_id, &components).map_err(|e| e.to_string())?;
+    let after = bundle_queries::find_bundle(&tx, bundle_id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "update", "bundle", Some(bundle_id), before.as_ref(), after.as_ref())
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(after)
+}
+
+#[tauri::command]
+pub async fn get_bundle(
+    app_handle: AppHandle,
+    bundle_id: i32,
+) -> Result<Bundle, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    bundle_queries::get_bundle(&conn, bundle_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_bundles(app_handle: AppHandle) -> Result<Vec<Bundle>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    bundle_queries::get_bundles(&conn)
+        .map_err(|e| e.to_string())
+}
+
+/* ========== INVENTARIO ========== */
+#[tauri::command]
+pub async fn record_inventory_movement(
+    app_handle: AppHandle,
+    actor_id: i32,
+    mut movement: NewInventoryMovement,
+    unit_id: Option<i32>,
+) -> Result<InventoryMovement, String> {
+    if !movement.movement_type.is_manual() {
+        return Err("Solo se pueden registrar compras, ajustes y traspasos manualmente".to_string());
+    }
+    if movement.movement_type == MovementType::Adjustment
+        && movement.reason.as_deref().map(str::trim).unwrap_or_default().is_empty()
+    {
+        return Err("Los ajustes de inventario requieren un motivo".to_string());
+    }
+
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    // Las compras pueden capturarse en cajas; el kárdex siempre va en la unidad base
+    movement.quantity = product_queries::to_base_quantity(&tx, movement.product_id, unit_id, movement.quantity)
+        .map_err(|e| e.to_string())?;
+    let product = product_queries::get_product(&tx, movement.product_id).map_err(|e| e.to_string())?;
+    product.validate_quantity(movement.quantity.abs())?;
+    let id = inventory_queries::record_movement(&tx, Some(actor_id), &movement).map_err(|e| e.to_string())?;
+    let after = inventory_queries::get_movement(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "create", "inventory_movement", Some(id), None, Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(after)
+}
+
+#[tauri::command]
+pub async fn get_product_kardex(
+    app_handle: AppHandle,
+    product_id: i32,
+    start: Option<String>,
+    end: Option<String>,
+) -> Result<Vec<InventoryMovement>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    let start_date = start
+        .map(|date| NaiveDateTime::parse_from_str(&date, "%Y-%m-%d %H:%M:%S"))
+        .transpose()
+        .map_err(|e| format!("Invalid start date format: {}", e))?;
+    let end_date = end
+        .map(|date| NaiveDateTime::parse_from_str(&date, "%Y-%m-%d %H:%M:%S"))
+        .transpose()
+        .map_err(|e| format!("Invalid end date format: {}", e))?;
+
+    inventory_queries::get_product_kardex(&conn, product_id, start_date, end_date)
         .map_err(|e| e.to_string())
 }
 
@@ -73,11 +585,18 @@ pub async fn delete_product(
 #[tauri::command]
 pub async fn add_customer(
     app_handle: AppHandle,
+    actor_id: i32,
     customer: NewCustomer,
 ) -> Result<i32, String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    customer_queries::create_customer(&conn, customer)
-        .map_err(|e| e.to_string())
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let id = customer_queries::create_customer(&tx, customer).map_err(|e| e.to_string())?;
+    let after = customer_queries::get_customer(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "create", "customer", Some(id), None, Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(id)
 }
 
 #[tauri::command]
@@ -97,35 +616,66 @@ pub async fn get_all_customers(app_handle: AppHandle) -> Result<Vec<Customer>, S
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn search_customers(
+    app_handle: AppHandle,
+    text: String,
+    limit: Option<u32>,
+) -> Result<Vec<CustomerMatch>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    customer_queries::search_customers(&conn, &text, search_limit(limit))
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn update_customer(
     app_handle: AppHandle,
+    actor_id: i32,
     id: i32,
     customer: NewCustomer,
 ) -> Result<(), String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    customer_queries::update_customer(&conn, id, customer)
-        .map_err(|e| e.to_string())
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = customer_queries::get_customer(&tx, id).map_err(|e| e.to_string())?;
+    customer_queries::update_customer(&tx, id, customer).map_err(|e| e.to_string())?;
+    let after = customer_queries::get_customer(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "update", "customer", Some(id), Some(&before), Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub async fn delete_customer(
     app_handle: AppHandle,
+    actor_id: i32,
     id: i32,
 ) -> Result<(), String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    customer_queries::delete_customer(&conn, id)
-        .map_err(|e| e.to_string())
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = customer_queries::get_customer(&tx, id).map_err(|e| e.to_string())?;
+    customer_queries::delete_customer(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "delete", "customer", Some(id), Some(&before), None)
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
 }
 
 /* ========== VENTAS ========== */
 #[tauri::command]
 pub async fn create_sale(
     app_handle: AppHandle,
+    actor_id: i32,
     sale: NewSale,
 ) -> Result<Sale, String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    let id = sale_queries::create_sale(&conn, sale).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let id = sale_queries::create_sale(&tx, sale).map_err(|e| e.to_string())?;
+    let after = sale_queries::get_sale(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "create", "sale", Some(id), None, Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
     
     // Esperar un breve momento para asegurar que la transacción se complete
     tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -164,110 +714,550 @@ pub async fn get_sales_by_date_range(
 #[tauri::command]
 pub async fn cancel_sale(
     app_handle: AppHandle,
+    actor_id: i32,
     id: i32,
+    reason: String,
+    approval_token: Option<String>,
 ) -> Result<(), String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    sale_queries::cancel_sale(&conn, id)
-        .map_err(|e| e.to_string())
+    let actor = find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+
+    let required = approval_queries::required_for_cancel(&tx, id).map_err(|e| e.to_string())?;
+    let tokens: Vec<String> = approval_token.into_iter().collect();
+    approval_queries::authorize(&tx, &actor, &required, &tokens).map_err(|e| e.to_string())?;
+
+    let before = sale_queries::get_sale(&tx, id).map_err(|e| e.to_string())?;
+    sale_queries::cancel_sale(&tx, actor_id, id, &reason).map_err(|e| e.to_string())?;
+    let after = sale_queries::get_sale(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "cancel", "sale", Some(id), Some(&before), Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
 }
 
-/* ========== DETALLES DE VENTA ========== */
 #[tauri::command]
-pub async fn add_sale_detail(
+pub async fn complete_sale(
     app_handle: AppHandle,
-    detail: NewSaleDetail,
-) -> Result<i32, String> {
+    actor_id: i32,
+    sale_id: i32,
+    payments: Vec<NewSalePayment>,
+) -> Result<SaleCompletion, String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    sale_detail_queries::create_sale_detail(&conn, detail)
-        .map_err(|e| e.to_string())
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = sale_queries::get_sale(&tx, sale_id).map_err(|e| e.to_string())?;
+    let completion = sale_queries::complete_sale(&tx, actor_id, sale_id, &payments).map_err(|e| e.to_string())?;
+    let after = sale_queries::get_sale(&tx, sale_id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "complete", "sale", Some(sale_id), Some(&before), Some(&after))
+        .map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "create", "sale_payments", Some(sale_id), None, Some(&completion.payments))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    // Todo cobro con efectivo abre el cajón, se imprima o no el ticket. La venta
+    // ya quedó registrada, así que una falla del cajón no la revierte.
+    if completion.payments.iter().any(|payment| payment.method == PaymentMethod::Cash) {
+        let printer = settings_queries::get_printer_settings(&conn).map_err(|e| e.to_string())?;
+        if printer.enabled {
+            if let Err(e) = device::send(&printer.connection, &escpos::drawer_kick()) {
+                eprintln!("No se pudo abrir el cajón: {}", e);
+            }
+        }
+    }
+    Ok(completion)
 }
 
 #[tauri::command]
-pub async fn get_sale_details(
+pub async fn get_sale_payments(
     app_handle: AppHandle,
     sale_id: i32,
-) -> Result<Vec<SaleDetailWithProduct>, String> {
+) -> Result<Vec<SalePayment>, String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    sale_detail_queries::get_details_with_products_by_sale(&conn, sale_id)
+    payment_queries::get_payments_by_sale(&conn, sale_id)
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn remove_sale_detail(
+pub async fn get_tender_report(
     app_handle: AppHandle,
-    detail_id: i32,
-) -> Result<(), String> {
+    start: String,
+    end: String,
+) -> Result<Vec<TenderTotal>, String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    sale_detail_queries::delete_sale_detail(&conn, detail_id)
+    let start_date = NaiveDateTime::parse_from_str(&start, "%Y-%m-%d %H:%M:%S")
+        .map_err(|e| format!("Invalid start date format: {}", e))?;
+    let end_date = NaiveDateTime::parse_from_str(&end, "%Y-%m-%d %H:%M:%S")
+        .map_err(|e| format!("Invalid end date format: {}", e))?;
+
+    payment_queries::get_tender_report(&conn, start_date, end_date)
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn get_sale_details_basic(
+pub async fn get_category_sales_report(
     app_handle: AppHandle,
-    sale_id: i32,
-) -> Result<Vec<SaleDetail>, String> {
+    start: String,
+    end: String,
+) -> Result<Vec<CategorySales>, String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    sale_detail_queries::get_details_by_sale(&conn, sale_id)
+    let start_date = NaiveDateTime::parse_from_str(&start, "%Y-%m-%d %H:%M:%S")
+        .map_err(|e| format!("Invalid start date format: {}", e))?;
+    let end_date = NaiveDateTime::parse_from_str(&end, "%Y-%m-%d %H:%M:%S")
+        .map_err(|e| format!("Invalid end date format: {}", e))?;
+
+    category_queries::get_sales_by_category(&conn, start_date, end_date)
         .map_err(|e| e.to_string())
 }
 
-/* ========== FACTURAS ========== */
 #[tauri::command]
-pub async fn create_invoice(
+pub async fn get_template_sales_report(
     app_handle: AppHandle,
-    invoice: NewInvoice,
-) -> Result<i32, String> {
+    start: String,
+    end: String,
+) -> Result<Vec<TemplateSales>, String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    invoice_queries::create_invoice(&conn, invoice)
+    let start_date = NaiveDateTime::parse_from_str(&start, "%Y-%m-%d %H:%M:%S")
+        .map_err(|e| format!("Invalid start date format: {}", e))?;
+    let end_date = NaiveDateTime::parse_from_str(&end, "%Y-%m-%d %H:%M:%S")
+        .map_err(|e| format!("Invalid end date format: {}", e))?;
+
+    variant_queries::get_sales_by_template(&conn, start_date, end_date)
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn get_invoice_by_uuid(
-    app_handle: AppHandle,
-    uuid: String,
-) -> Result<Invoice, String> {
+pub async fn verify_sale_chain(app_handle: AppHandle) -> Result<ChainVerification, String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    invoice_queries::get_invoice_by_uuid(&conn, &uuid)
-        .map_err(|e| e.to_string())
+    sale_chain_queries::verify_chain(&conn).map_err(|e| e.to_string())
 }
 
+/* ========== CAJAS Y TURNOS ========== */
 #[tauri::command]
-pub async fn get_invoices_by_sale(
+pub async fn create_cash_register(
     app_handle: AppHandle,
-    sale_id: i32,
-) -> Result<Vec<Invoice>, String> {
+    actor_id: i32,
+    register: NewCashRegister,
+) -> Result<i32, String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    invoice_queries::get_invoices_by_sale(&conn, sale_id)
-        .map_err(|e| e.to_string())
+    let actor = find_actor(&conn, actor_id)?;
+    if !actor.role.is_supervisor() {
+        return Err("Solo administradores y gerentes pueden dar de alta cajas".to_string());
+    }
+
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let id = register_queries::create_register(&tx, register).map_err(|e| e.to_string())?;
+    let after = register_queries::get_register(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "create", "cash_register", Some(id), None, Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(id)
 }
 
 #[tauri::command]
-pub async fn cancel_invoice(
+pub async fn get_cash_registers(app_handle: AppHandle) -> Result<Vec<CashRegister>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    register_queries::get_all_registers(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn open_register_session(
     app_handle: AppHandle,
-    uuid: String,
-) -> Result<(), String> {
+    actor_id: i32,
+    session: OpenRegisterSession,
+) -> Result<RegisterSession, String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    invoice_queries::cancel_invoice(&conn, &uuid)
-        .map_err(|e| e.to_string())
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let id = register_queries::open_session(&tx, actor_id, &session).map_err(|e| e.to_string())?;
+    let after = register_queries::get_session(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "open", "register_session", Some(id), None, Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(after)
 }
 
-/* ========== USUARIOS ========== */
 #[tauri::command]
-pub async fn create_user(
+pub async fn get_current_register_session(
     app_handle: AppHandle,
-    user: NewUser,  // Asegúrate que esto coincide con lo que envía el frontend
-) -> Result<i32, String> {
-    println!("Datos recibidos para crear usuario: {:?}", user);
-    
+    user_id: i32,
+) -> Result<Option<RegisterSession>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    register_queries::get_open_session(&conn, user_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_register_session(
+    app_handle: AppHandle,
+    id: i32,
+) -> Result<RegisterSession, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    register_queries::get_session(&conn, id).map_err(|e| e.to_string())
+}
+
+// El cajero cierra su propio turno; un supervisor puede cerrar cualquiera
+#[tauri::command]
+pub async fn close_register_session(
+    app_handle: AppHandle,
+    actor_id: i32,
+    session_id: i32,
+    close: CloseRegisterSession,
+) -> Result<RegisterSession, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    let actor = find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+
+    let before = register_queries::get_session(&tx, session_id).map_err(|e| e.to_string())?;
+    if before.user_id != actor_id && !actor.role.is_supervisor() {
+        return Err("Solo el cajero del turno o un supervisor pueden cerrarlo".to_string());
+    }
+
+    register_queries::close_session(&tx, actor_id, session_id, &close).map_err(|e| e.to_string())?;
+    let after = register_queries::get_session(&tx, session_id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "close", "register_session", Some(session_id), Some(&before), Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(after)
+}
+
+#[tauri::command]
+pub async fn record_cash_movement(
+    app_handle: AppHandle,
+    actor_id: i32,
+    movement: NewCashMovement,
+) -> Result<CashMovement, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let id = register_queries::create_cash_movement(&tx, actor_id, &movement).map_err(|e| e.to_string())?;
+    let after = register_queries::get_cash_movement(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "create", "cash_movement", Some(id), None, Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(after)
+}
+
+#[tauri::command]
+pub async fn get_cash_movements(
+    app_handle: AppHandle,
+    session_id: i32,
+) -> Result<Vec<CashMovement>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    register_queries::get_cash_movements(&conn, session_id).map_err(|e| e.to_string())
+}
+
+/* ========== CORTES X Y Z ========== */
+// Sin fecha se toma el día en curso
+fn report_date(conn: &rusqlite::Connection, date: Option<String>) -> Result<String, String> {
+    match date {
+        Some(date) => Ok(date),
+        None => report_queries::today(conn).map_err(|e| e.to_string()),
+    }
+}
+
+#[tauri::command]
+pub async fn get_x_report(
+    app_handle: AppHandle,
+    actor_id: i32,
+    register_id: i32,
+    date: Option<String>,
+) -> Result<RegisterReport, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    let actor = find_actor(&conn, actor_id)?;
+    if !actor.role.is_supervisor() {
+        return Err("Solo administradores y gerentes pueden consultar cortes".to_string());
+    }
+    let business_date = report_date(&conn, date)?;
+    report_queries::build_report(&conn, register_id, &business_date).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn generate_z_report(
+    app_handle: AppHandle,
+    actor_id: i32,
+    register_id: i32,
+    date: Option<String>,
+) -> Result<RegisterReport, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    let actor = find_actor(&conn, actor_id)?;
+    if !actor.role.is_supervisor() {
+        return Err("Solo administradores y gerentes pueden hacer el corte Z".to_string());
+    }
+
+    let business_date = report_date(&conn, date)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let report = report_queries::create_z_report(&tx, actor_id, register_id, &business_date)
+        .map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "create", "z_report", report.z_number.map(|n| n as i32), None, Some(&report))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+#[tauri::command]
+pub async fn get_z_report(
+    app_handle: AppHandle,
+    z_number: i64,
+) -> Result<RegisterReport, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    report_queries::get_z_report(&conn, z_number).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_z_reports(
+    app_handle: AppHandle,
+    register_id: Option<i32>,
+) -> Result<Vec<ZReportSummary>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    report_queries::get_z_reports(&conn, register_id).map_err(|e| e.to_string())
+}
+
+/* ========== IMPRESIÓN ========== */
+#[tauri::command]
+pub async fn print_receipt(
+    app_handle: AppHandle,
+    actor_id: i32,
+    sale_id: i32,
+) -> Result<(), String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let printer = settings_queries::get_printer_settings(&conn).map_err(|e| e.to_string())?;
+    if !printer.enabled {
+        return Err("La impresora de tickets no está habilitada".to_string());
+    }
+
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let is_copy = receipt_queries::is_reprint(&tx, sale_id).map_err(|e| e.to_string())?;
+    let bytes = receipt::render_sale_receipt(&tx, sale_id, is_copy).map_err(|e| e.to_string())?;
+    device::send(&printer.connection, &bytes)
+        .map_err(|e| format!("No se pudo imprimir el ticket: {}", e))?;
+
+    receipt_queries::record_print(&tx, sale_id, actor_id, is_copy).map_err(|e| e.to_string())?;
+    if is_copy {
+        audit_queries::record_action(&tx, Some(actor_id), "reprint", "sale", Some(sale_id))
+            .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_printer_status(app_handle: AppHandle) -> Result<PrinterStatus, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    let printer = settings_queries::get_printer_settings(&conn).map_err(|e| e.to_string())?;
+    device::query_status(&printer.connection)
+        .map_err(|e| format!("No se pudo consultar la impresora: {}", e))
+}
+
+// Apertura manual del cajón, fuera de un cobro en efectivo
+#[tauri::command]
+pub async fn open_cash_drawer(
+    app_handle: AppHandle,
+    actor_id: i32,
+) -> Result<(), String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    let actor = find_actor(&conn, actor_id)?;
+    if !actor.role.is_supervisor() {
+        return Err("Solo administradores y gerentes pueden abrir el cajón manualmente".to_string());
+    }
+
+    let printer = settings_queries::get_printer_settings(&conn).map_err(|e| e.to_string())?;
+    device::send(&printer.connection, &escpos::drawer_kick())
+        .map_err(|e| format!("No se pudo abrir el cajón: {}", e))?;
+
+    let session_id = register_queries::get_open_session(&conn, actor_id)
+        .map_err(|e| e.to_string())?
+        .map(|session| session.id);
+    audit_queries::record_action(&conn, Some(actor_id), "open_drawer", "register_session", session_id)
+        .map_err(|e| e.to_string())
+}
+
+/* ========== DETALLES DE VENTA ========== */
+#[tauri::command]
+pub async fn add_sale_detail(
+    app_handle: AppHandle,
+    actor_id: i32,
+    detail: NewSaleDetail,
+    approval_tokens: Option<Vec<String>>,
+) -> Result<i32, CommandError> {
+    let conn = establish_connection(&app_handle)?;
+    let actor = find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction()?;
+
+    let required = approval_queries::required_for_line(&tx, &detail)?;
+    approval_queries::authorize(&tx, &actor, &required, &approval_tokens.unwrap_or_default())?;
+
+    let id = sale_detail_queries::create_sale_detail(&tx, actor_id, detail)?;
+    let after = sale_detail_queries::get_sale_detail(&tx, id)?;
+    audit_queries::record(&tx, Some(actor_id), "create", "sale_detail", Some(id), None, Some(&after))?;
+    tx.commit()?;
+    Ok(id)
+}
+
+// Línea desde el escáner; las etiquetas de báscula con precio conservan el
+// importe impreso como subtotal
+#[tauri::command]
+pub async fn add_scanned_sale_detail(
+    app_handle: AppHandle,
+    actor_id: i32,
+    sale_id: i32,
+    code: String,
+) -> Result<i32, CommandError> {
+    let conn = establish_connection(&app_handle)?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction()?;
+
+    let id = sale_detail_queries::create_scanned_sale_detail(&tx, actor_id, sale_id, &code)?;
+    let after = sale_detail_queries::get_sale_detail(&tx, id)?;
+    audit_queries::record(&tx, Some(actor_id), "create", "sale_detail", Some(id), None, Some(&after))?;
+    tx.commit()?;
+    Ok(id)
+}
+
+#[tauri::command]
+pub async fn get_sale_details(
+    app_handle: AppHandle,
+    sale_id: i32,
+) -> Result<Vec<SaleDetailWithProduct>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    sale_detail_queries::get_details_with_products_by_sale(&conn, sale_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn remove_sale_detail(
+    app_handle: AppHandle,
+    actor_id: i32,
+    detail_id: i32,
+    approval_token: Option<String>,
+) -> Result<(), String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    let actor = find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+
+    let required = approval_queries::required_for_void(&tx, detail_id).map_err(|e| e.to_string())?;
+    let tokens: Vec<String> = approval_token.into_iter().collect();
+    approval_queries::authorize(&tx, &actor, &required, &tokens).map_err(|e| e.to_string())?;
+
+    let before = sale_detail_queries::get_sale_detail(&tx, detail_id).map_err(|e| e.to_string())?;
+    sale_detail_queries::delete_sale_detail(&tx, actor_id, detail_id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "delete", "sale_detail", Some(detail_id), Some(&before), None)
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_sale_details_basic(
+    app_handle: AppHandle,
+    sale_id: i32,
+) -> Result<Vec<SaleDetail>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    sale_detail_queries::get_details_by_sale(&conn, sale_id)
+        .map_err(|e| e.to_string())
+}
+
+/* ========== DEVOLUCIONES ========== */
+#[tauri::command]
+pub async fn create_return(
+    app_handle: AppHandle,
+    actor_id: i32,
+    sale_return: NewSaleReturn,
+) -> Result<SaleReturn, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let id = return_queries::create_return(&tx, actor_id, &sale_return).map_err(|e| e.to_string())?;
+    let after = return_queries::get_return(&tx, id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "create", "return", Some(id), None, Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(after)
+}
+
+#[tauri::command]
+pub async fn get_returns_by_sale(
+    app_handle: AppHandle,
+    sale_id: i32,
+) -> Result<Vec<SaleReturn>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    return_queries::get_returns_by_sale(&conn, sale_id)
+        .map_err(|e| e.to_string())
+}
+
+/* ========== FACTURAS ========== */
+#[tauri::command]
+pub async fn create_invoice(
+    app_handle: AppHandle,
+    actor_id: i32,
+    invoice: NewInvoice,
+) -> Result<i32, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let uuid = invoice.uuid.clone();
+    let id = invoice_queries::create_invoice(&tx, invoice).map_err(|e| e.to_string())?;
+    let after = invoice_queries::get_invoice_by_uuid(&tx, &uuid).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "create", "invoice", Some(id), None, Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+#[tauri::command]
+pub async fn get_invoice_by_uuid(
+    app_handle: AppHandle,
+    uuid: String,
+) -> Result<Invoice, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    invoice_queries::get_invoice_by_uuid(&conn, &uuid)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_invoices_by_sale(
+    app_handle: AppHandle,
+    sale_id: i32,
+) -> Result<Vec<Invoice>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    invoice_queries::get_invoices_by_sale(&conn, sale_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn cancel_invoice(
+    app_handle: AppHandle,
+    actor_id: i32,
+    uuid: String,
+) -> Result<(), String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = invoice_queries::get_invoice_by_uuid(&tx, &uuid).map_err(|e| e.to_string())?;
+    invoice_queries::cancel_invoice(&tx, &uuid).map_err(|e| e.to_string())?;
+    let after = invoice_queries::get_invoice_by_uuid(&tx, &uuid).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "cancel", "invoice", Some(before.id), Some(&before), Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/* ========== USUARIOS ========== */
+#[tauri::command]
+pub async fn create_user(
+    app_handle: AppHandle,
+    actor_id: i32,
+    user: NewUser,  // Asegúrate que esto coincide con lo que envía el frontend
+) -> Result<i32, String> {
     let conn = establish_connection(&app_handle).map_err(|e| {
         eprintln!("Error de conexión: {}", e);
         e.to_string()
     })?;
+    require_admin(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
     
-    match user_queries::create_user(&conn, user) {
+    match user_queries::create_user(&tx, user) {
         Ok(id) => {
+            let after = find_user(&tx, id)?;
+            audit_queries::record(&tx, Some(actor_id), "create", "user", Some(id), None, Some(&after))
+                .map_err(|e| e.to_string())?;
+            tx.commit().map_err(|e| e.to_string())?;
             println!("Usuario creado con ID: {}", id);
             Ok(id)
         },
@@ -283,31 +1273,93 @@ pub async fn authenticate_user(
     app_handle: AppHandle,
     username: String,
     password: String,
-) -> Result<Option<User>, String> {
+) -> Result<AuthResult, String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    user_queries::authenticate_user(&conn, &username, &password)
+    match user_queries::authenticate_user(&conn, &username, &password).map_err(|e| e.to_string())? {
+        Some(user) => two_factor_queries::login_result(&conn, user).map_err(|e| e.to_string()),
+        None => Ok(AuthResult::Failed),
+    }
+}
+
+#[tauri::command]
+pub async fn verify_two_factor(
+    app_handle: AppHandle,
+    challenge_token: String,
+    code: String,
+) -> Result<User, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    two_factor_queries::complete_login(&conn, &challenge_token, &code)
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub async fn update_user_password(
     app_handle: AppHandle,
+    actor_id: i32,
     user_id: i32,
     new_password: String,
 ) -> Result<(), String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    user_queries::update_user_password(&conn, user_id, &new_password)
-        .map_err(|e| e.to_string())
+    require_admin(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    user_queries::update_user_password(&tx, user_id, &new_password, true)
+        .map_err(|e| e.to_string())?;
+    audit_queries::record_action(&tx, Some(actor_id), "password_reset", "user", Some(user_id))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn change_own_password(
+    app_handle: AppHandle,
+    user_id: i32,
+    old_password: String,
+    new_password: String,
+) -> Result<(), String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    user_queries::change_own_password(&tx, user_id, &old_password, &new_password)
+        .map_err(|e| e.to_string())?;
+    audit_queries::record_action(&tx, Some(user_id), "password_change", "user", Some(user_id))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn force_password_change(
+    app_handle: AppHandle,
+    actor_id: i32,
+    user_id: i32,
+) -> Result<(), String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    require_admin(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = find_user(&tx, user_id)?;
+    user_queries::require_password_change(&tx, user_id).map_err(|e| e.to_string())?;
+    let after = find_user(&tx, user_id)?;
+    audit_queries::record(&tx, Some(actor_id), "update", "user", Some(user_id), Some(&before), Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub async fn deactivate_user(
     app_handle: AppHandle,
+    actor_id: i32,
     user_id: i32,
 ) -> Result<(), String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    user_queries::deactivate_user(&conn, user_id)
-        .map_err(|e| e.to_string())
+    require_admin(&conn, actor_id)?;
+    if actor_id == user_id {
+        return Err("No puede desactivar su propia cuenta".to_string());
+    }
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = find_user(&tx, user_id)?;
+    user_queries::deactivate_user(&tx, user_id).map_err(|e| e.to_string())?;
+    let after = find_user(&tx, user_id)?;
+    audit_queries::record(&tx, Some(actor_id), "update", "user", Some(user_id), Some(&before), Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -330,14 +1382,424 @@ pub async fn get_all_users(app_handle: tauri::AppHandle) -> Result<Vec<User>, St
 }
 
 #[tauri::command]
-pub async fn update_user(app_handle: tauri::AppHandle, user: User) -> Result<(), String> {
+pub async fn update_user(app_handle: tauri::AppHandle, actor_id: i32, user: User) -> Result<(), String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    require_admin(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let id = user.id;
+    let before = find_user(&tx, id)?;
+    // Un administrador no puede quitarse el rol ni desactivarse a sí mismo
+    if id == actor_id && (user.role != before.role || user.is_active != before.is_active) {
+        return Err("No puede cambiar el rol ni el estado de su propia cuenta".to_string());
+    }
+    user_queries::update_user(&tx, user).map_err(|e| e.to_string())?;
+    let after = find_user(&tx, id)?;
+    audit_queries::record(&tx, Some(actor_id), "update", "user", Some(id), Some(&before), Some(&after))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_user(app_handle: tauri::AppHandle, actor_id: i32, user_id: i32) -> Result<usize, String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    user_queries::update_user(&conn, user).map_err(|e| e.to_string())
+    require_admin(&conn, actor_id)?;
+    if actor_id == user_id {
+        return Err("No puede eliminar su propia cuenta".to_string());
+    }
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = find_user(&tx, user_id)?;
+    two_factor_queries::disable(&tx, user_id).map_err(|e| e.to_string())?;
+    let rows_affected = user_queries::delete_user(&tx, user_id).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "delete", "user", Some(user_id), Some(&before), None)
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(rows_affected)
 }
 
 #[tauri::command]
-pub async fn delete_user(app_handle: tauri::AppHandle, user_id: i32) -> Result<usize, String> {
+pub async fn set_user_pin(
+    app_handle: AppHandle,
+    actor_id: i32,
+    user_id: i32,
+    pin: String,
+    current_password: Option<String>,
+) -> Result<(), String> {
     let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
-    user_queries::delete_user(&conn, user_id)
+    let actor = find_actor(&conn, actor_id)?;
+    authorize_account_change(&conn, &actor, user_id, current_password.as_deref())?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    user_queries::set_user_pin(&tx, user_id, &pin).map_err(|e| e.to_string())?;
+    audit_queries::record_action(&tx, Some(actor_id), "pin_change", "user", Some(user_id))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/* ========== AUTORIZACIONES DE SUPERVISOR ========== */
+#[tauri::command]
+pub async fn request_supervisor_approval(
+    app_handle: AppHandle,
+    actor_id: i32,
+    supervisor_username: String,
+    credential: String,
+    request: ApprovalRequest,
+) -> Result<SupervisorApproval, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    find_actor(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    match approval_queries::grant(&tx, actor_id, &supervisor_username, &credential, &request) {
+        Ok(approval) => {
+            audit_queries::record(&tx, Some(approval.approved_by), "approve", "sale", Some(request.sale_id), None, Some(&request))
+                .map_err(|e| e.to_string())?;
+            tx.commit().map_err(|e| e.to_string())?;
+            Ok(approval)
+        }
+        Err(e) => {
+            // El intento fallido se confirma para que cuente hacia el bloqueo
+            let attempt = serde_json::json!({ "supervisor": supervisor_username, "request": request });
+            audit_queries::record(&tx, Some(actor_id), "approval_denied", "sale", Some(request.sale_id), None, Some(&attempt))
+                .map_err(|e| e.to_string())?;
+            tx.commit().map_err(|e| e.to_string())?;
+            Err(e.to_string())
+        }
+    }
+}
+
+/* ========== VERIFICACIÓN EN DOS PASOS ========== */
+#[tauri::command]
+pub async fn get_two_factor_status(
+    app_handle: AppHandle,
+    user_id: i32,
+) -> Result<TwoFactorStatus, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    let user = find_user(&conn, user_id)?;
+    two_factor_queries::get_status(&conn, &user).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn begin_two_factor_enrollment(
+    app_handle: AppHandle,
+    actor_id: i32,
+    user_id: i32,
+    password: Option<String>,
+) -> Result<TwoFactorEnrollment, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    let actor = find_enrolling_actor(&conn, actor_id, user_id)?;
+    authorize_account_change(&conn, &actor, user_id, password.as_deref())?;
+    let user = find_user(&conn, user_id)?;
+    two_factor_queries::begin_enrollment(&conn, &user).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn confirm_two_factor_enrollment(
+    app_handle: AppHandle,
+    actor_id: i32,
+    user_id: i32,
+    code: String,
+) -> Result<Vec<String>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    // El código ya demuestra que el dispositivo está en manos de quien inscribe
+    let actor = find_enrolling_actor(&conn, actor_id, user_id)?;
+    if actor.id != user_id && actor.role != UserRole::Admin {
+        return Err("Solo el propio usuario o un administrador pueden modificar esta cuenta".to_string());
+    }
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let codes = two_factor_queries::confirm_enrollment(&tx, user_id, &code)
+        .map_err(|e| e.to_string())?;
+    audit_queries::record_action(&tx, Some(actor_id), "two_factor_enable", "user", Some(user_id))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(codes)
+}
+
+#[tauri::command]
+pub async fn regenerate_recovery_codes(
+    app_handle: AppHandle,
+    actor_id: i32,
+    user_id: i32,
+    code: String,
+) -> Result<Vec<String>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    // Los códigos se muestran a quien los genera, así que solo el titular puede hacerlo
+    find_actor(&conn, actor_id)?;
+    if actor_id != user_id {
+        return Err("Solo el propio usuario puede generar sus códigos de recuperación".to_string());
+    }
+    if !two_factor_queries::verify_user_code(&conn, user_id, &code).map_err(|e| e.to_string())? {
+        return Err("Código de verificación inválido".to_string());
+    }
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let codes = two_factor_queries::generate_recovery_codes(&tx, user_id)
+        .map_err(|e| e.to_string())?;
+    audit_queries::record_action(&tx, Some(user_id), "recovery_codes_regenerate", "user", Some(user_id))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(codes)
+}
+
+#[tauri::command]
+pub async fn disable_two_factor(
+    app_handle: AppHandle,
+    actor_id: i32,
+    user_id: i32,
+    code: Option<String>,
+) -> Result<(), String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    let actor = find_actor(&conn, actor_id)?;
+    let user = find_user(&conn, user_id)?;
+    if actor.id == user_id {
+        if two_factor_queries::is_required(&conn, user.role).map_err(|e| e.to_string())? {
+            return Err("La verificación en dos pasos es obligatoria para este rol".to_string());
+        }
+        let code = code.ok_or_else(|| "Capture un código de verificación".to_string())?;
+        if !two_factor_queries::verify_user_code(&conn, user_id, &code).map_err(|e| e.to_string())? {
+            return Err("Código de verificación inválido".to_string());
+        }
+    } else if actor.role != UserRole::Admin {
+        return Err("Solo el propio usuario o un administrador pueden modificar esta cuenta".to_string());
+    }
+    // Un administrador puede restablecer el segundo factor de otro usuario (p. ej.
+    // por pérdida del teléfono); si su rol lo exige, se le pedirá inscribirse de nuevo.
+
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    two_factor_queries::disable(&tx, user_id).map_err(|e| e.to_string())?;
+    audit_queries::record_action(&tx, Some(actor_id), "two_factor_disable", "user", Some(user_id))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/* ========== AUDITORÍA ========== */
+#[tauri::command]
+pub async fn get_audit_log(
+    app_handle: AppHandle,
+    actor_id: i32,
+    filter: Option<AuditLogFilter>,
+) -> Result<Vec<AuditEntry>, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    require_admin(&conn, actor_id)?;
+    audit_queries::get_audit_log(&conn, &filter.unwrap_or_default())
         .map_err(|e| e.to_string())
 }
+
+/* ========== CONFIGURACIÓN INICIAL ========== */
+#[tauri::command]
+pub async fn get_setup_status(app_handle: AppHandle) -> Result<SetupStatus, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    let provisioned = settings_queries::is_provisioned(&conn).map_err(|e| e.to_string())?;
+    Ok(SetupStatus { provisioned })
+}
+
+#[tauri::command]
+pub async fn setup_system(
+    app_handle: AppHandle,
+    setup: SystemSetup,
+) -> Result<User, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+
+    // El asistente solo puede ejecutarse una vez, sobre una base de datos vacía
+    if settings_queries::is_provisioned(&tx).map_err(|e| e.to_string())?
+        || user_queries::count_users(&tx).map_err(|e| e.to_string())? > 0
+    {
+        return Err("El sistema ya fue configurado".to_string());
+    }
+
+    if setup.admin.username.trim().is_empty() {
+        return Err("El nombre de usuario del administrador es obligatorio".to_string());
+    }
+
+    let admin_id = user_queries::create_user(&tx, NewUser {
+        username: setup.admin.username,
+        email: setup.admin.email,
+        password: setup.admin.password,
+        role: UserRole::Admin,
+        full_name: setup.admin.full_name,
+    }).map_err(|e| e.to_string())?;
+
+    settings_queries::save_business_settings(&tx, &setup.business).map_err(|e| e.to_string())?;
+    settings_queries::save_tax_settings(&tx, &setup.tax).map_err(|e| e.to_string())?;
+    settings_queries::mark_provisioned(&tx).map_err(|e| e.to_string())?;
+
+    let admin = find_user(&tx, admin_id)?;
+    audit_queries::record(&tx, Some(admin_id), "setup", "user", Some(admin_id), None, Some(&admin))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(admin)
+}
+
+/* ========== AJUSTES ========== */
+#[tauri::command]
+pub async fn get_business_settings(app_handle: AppHandle) -> Result<BusinessSettings, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    settings_queries::get_business_settings(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn update_business_settings(
+    app_handle: AppHandle,
+    actor_id: i32,
+    settings: BusinessSettings,
+) -> Result<(), String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    require_admin(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = settings_queries::get_business_settings(&tx).map_err(|e| e.to_string())?;
+    settings_queries::save_business_settings(&tx, &settings).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "update", "settings.business", None, Some(&before), Some(&settings))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_tax_settings(app_handle: AppHandle) -> Result<TaxSettings, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    settings_queries::get_tax_settings(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn update_tax_settings(
+    app_handle: AppHandle,
+    actor_id: i32,
+    settings: TaxSettings,
+) -> Result<(), String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    require_admin(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = settings_queries::get_tax_settings(&tx).map_err(|e| e.to_string())?;
+    settings_queries::save_tax_settings(&tx, &settings).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "update", "settings.tax", None, Some(&before), Some(&settings))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_password_policy(app_handle: AppHandle) -> Result<PasswordPolicy, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    settings_queries::get_password_policy(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn update_password_policy(
+    app_handle: AppHandle,
+    actor_id: i32,
+    policy: PasswordPolicy,
+) -> Result<(), String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    require_admin(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = settings_queries::get_password_policy(&tx).map_err(|e| e.to_string())?;
+    settings_queries::save_password_policy(&tx, &policy).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "update", "settings.password_policy", None, Some(&before), Some(&policy))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_two_factor_policy(app_handle: AppHandle) -> Result<TwoFactorPolicy, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    settings_queries::get_two_factor_policy(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn update_two_factor_policy(
+    app_handle: AppHandle,
+    actor_id: i32,
+    policy: TwoFactorPolicy,
+) -> Result<(), String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    require_admin(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = settings_queries::get_two_factor_policy(&tx).map_err(|e| e.to_string())?;
+    settings_queries::save_two_factor_policy(&tx, &policy).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "update", "settings.two_factor_policy", None, Some(&before), Some(&policy))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_pos_restrictions(app_handle: AppHandle) -> Result<PosRestrictions, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    settings_queries::get_pos_restrictions(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn update_pos_restrictions(
+    app_handle: AppHandle,
+    actor_id: i32,
+    restrictions: PosRestrictions,
+) -> Result<(), String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    require_admin(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = settings_queries::get_pos_restrictions(&tx).map_err(|e| e.to_string())?;
+    settings_queries::save_pos_restrictions(&tx, &restrictions).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "update", "settings.pos_restrictions", None, Some(&before), Some(&restrictions))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_printer_settings(app_handle: AppHandle) -> Result<PrinterSettings, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    settings_queries::get_printer_settings(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn update_printer_settings(
+    app_handle: AppHandle,
+    actor_id: i32,
+    settings: PrinterSettings,
+) -> Result<(), String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    require_admin(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = settings_queries::get_printer_settings(&tx).map_err(|e| e.to_string())?;
+    settings_queries::save_printer_settings(&tx, &settings).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "update", "settings.printer", None, Some(&before), Some(&settings))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_scale_barcode_settings(app_handle: AppHandle) -> Result<ScaleBarcodeSettings, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    settings_queries::get_scale_barcode_settings(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn update_scale_barcode_settings(
+    app_handle: AppHandle,
+    actor_id: i32,
+    settings: ScaleBarcodeSettings,
+) -> Result<(), String> {
+    settings.validate()?;
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    require_admin(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = settings_queries::get_scale_barcode_settings(&tx).map_err(|e| e.to_string())?;
+    settings_queries::save_scale_barcode_settings(&tx, &settings).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "update", "settings.scale_barcodes", None, Some(&before), Some(&settings))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_inventory_settings(app_handle: AppHandle) -> Result<InventorySettings, String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    settings_queries::get_inventory_settings(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn update_inventory_settings(
+    app_handle: AppHandle,
+    actor_id: i32,
+    settings: InventorySettings,
+) -> Result<(), String> {
+    let conn = establish_connection(&app_handle).map_err(|e| e.to_string())?;
+    require_admin(&conn, actor_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let before = settings_queries::get_inventory_settings(&tx).map_err(|e| e.to_string())?;
+    settings_queries::save_inventory_settings(&tx, &settings).map_err(|e| e.to_string())?;
+    audit_queries::record(&tx, Some(actor_id), "update", "settings.inventory", None, Some(&before), Some(&settings))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
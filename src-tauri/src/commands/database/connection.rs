@@ -1,8 +1,10 @@
 use std::fs;
 use tauri::{AppHandle, Manager};
 use rusqlite::{Connection, Result};
-use crate::commands::database::models::user::{NewUser, UserRole};
-use crate::commands::database::queries::user_queries;
+use crate::commands::database::queries::{settings_queries, user_queries};
+
+const LEGACY_ADMIN_USERNAME: &str = "AdminSalesys";
+const LEGACY_ADMIN_PASSWORD: &str = "admin";
 
 pub fn get_db_path(app_handle: &AppHandle) -> String {
     let app_data_dir = app_handle
@@ -24,39 +26,188 @@ pub fn establish_connection(app_handle: &AppHandle) -> Result<Connection> {
 
 pub fn initialize_database(app_handle: &AppHandle) -> Result<()> {
     let conn = establish_connection(app_handle)?;
+    create_tables(&conn)?;
+    migrate_legacy_installation(&conn)?;
     
-    // Ejecutar todas las creaciones de tablas
-    crate::commands::database::queries::product_queries::create_table(&conn)?;
-    crate::commands::database::queries::customer_queries::create_table(&conn)?;
-    crate::commands::database::queries::sale_queries::create_table(&conn)?;
-    crate::commands::database::queries::sale_detail_queries::create_table(&conn)?;
-    crate::commands::database::queries::invoice_queries::create_table(&conn)?;
-    crate::commands::database::queries::user_queries::create_table(&conn)?;
-
-    check_and_create_default_admin(&conn)?;
-    
     Ok(())
 }
 
-fn check_and_create_default_admin(conn: &Connection) -> rusqlite::Result<()> {
-    let user_count: i64 = conn.query_row(
-        "SELECT COUNT(*) FROM users",
+// Ejecutar todas las creaciones de tablas; el orden importa para las
+// migraciones que copian datos de una tabla a otra
+pub fn create_tables(conn: &Connection) -> Result<()> {
+    crate::commands::database::queries::product_queries::create_table(conn)?;
+    crate::commands::database::queries::customer_queries::create_table(conn)?;
+    crate::commands::database::queries::sale_queries::create_table(conn)?;
+    crate::commands::database::queries::sale_chain_queries::create_table(conn)?;
+    crate::commands::database::queries::sale_detail_queries::create_table(conn)?;
+    crate::commands::database::queries::invoice_queries::create_table(conn)?;
+    crate::commands::database::queries::user_queries::create_table(conn)?;
+    crate::commands::database::queries::settings_queries::create_table(conn)?;
+    crate::commands::database::queries::two_factor_queries::create_table(conn)?;
+    crate::commands::database::queries::approval_queries::create_table(conn)?;
+    crate::commands::database::queries::audit_queries::create_table(conn)?;
+    crate::commands::database::queries::inventory_queries::create_table(conn)?;
+    crate::commands::database::queries::return_queries::create_table(conn)?;
+    crate::commands::database::queries::payment_queries::create_table(conn)?;
+    crate::commands::database::queries::register_queries::create_table(conn)?;
+    crate::commands::database::queries::report_queries::create_table(conn)?;
+    crate::commands::database::queries::receipt_queries::create_table(conn)?;
+    crate::commands::database::queries::category_queries::create_table(conn)?;
+    crate::commands::database::queries::variant_queries::create_table(conn)?;
+    crate::commands::database::queries::bundle_queries::create_table(conn)?;
+    Ok(())
+}
+
+// Instalaciones anteriores al asistente de configuración ya tienen usuarios:
+// se marcan como configuradas y, si el administrador sigue con la contraseña
+// por defecto, se le obliga a cambiarla en el siguiente inicio de sesión.
+fn migrate_legacy_installation(conn: &Connection) -> rusqlite::Result<()> {
+    if settings_queries::is_provisioned(conn)? || user_queries::count_users(conn)? == 0 {
+        return Ok(());
+    }
+
+    settings_queries::mark_provisioned(conn)?;
+
+    if let Some(admin) = user_queries::authenticate_user(conn, LEGACY_ADMIN_USERNAME, LEGACY_ADMIN_PASSWORD)? {
+        user_queries::require_password_change(conn, admin.id)?;
+    }
+
+    Ok(())
+}
+
+fn table_columns(conn: &Connection, table: &str) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(columns)
+}
+
+pub fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    if !table_columns(conn, table)?.iter().any(|name| name == column) {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+// SQLite no permite modificar restricciones CHECK de una tabla existente. Si la
+// definición guardada no contiene `marker`, se crea la tabla nueva con
+// `table_sql`, se copian las columnas comunes y se reemplaza la anterior.
+pub fn rebuild_table_if_outdated(
+    conn: &Connection,
+    table: &str,
+    marker: &str,
+    table_sql: fn(&str) -> String,
+) -> rusqlite::Result<()> {
+    let current_sql: String = conn.query_row(
+        "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?",
+        [table],
+        |row| row.get(0),
+    )?;
+    if current_sql.contains(marker) {
+        return Ok(());
+    }
+
+    // Con las llaves foráneas activas, DROP TABLE borraría en cascada las
+    // filas que la referencian; el pragma solo puede cambiarse fuera de la
+    // transacción.
+    let foreign_keys: bool = conn.query_row("PRAGMA foreign_keys", [], |row| row.get(0))?;
+    conn.execute_batch("PRAGMA foreign_keys = OFF")?;
+    let result = rebuild_table(conn, table, table_sql);
+    if foreign_keys {
+        conn.execute_batch("PRAGMA foreign_keys = ON")?;
+    }
+    result
+}
+
+fn rebuild_table(conn: &Connection, table: &str, table_sql: fn(&str) -> String) -> rusqlite::Result<()> {
+    let new_table = format!("{}_new", table);
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(&table_sql(&new_table), [])?;
+
+    let new_columns = table_columns(&tx, &new_table)?;
+    let common = table_columns(&tx, table)?
+        .into_iter()
+        .filter(|column| new_columns.contains(column))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    tx.execute(
+        &format!("INSERT INTO {} ({}) SELECT {} FROM {}", new_table, common, common, table),
         [],
-        |row| row.get(0)
     )?;
+    tx.execute(&format!("DROP TABLE {}", table), [])?;
+    tx.execute(&format!("ALTER TABLE {} RENAME TO {}", new_table, table), [])?;
+    tx.commit()
+}
+
+// Índice de texto completo con contenido externo: `<table>_fts` se mantiene
+// al día con disparadores y, al crearse, se llena con las filas existentes.
+// El tokenizador ignora acentos para que "cafe" encuentre "café".
+pub fn create_fts_index(conn: &Connection, table: &str, columns: &[&str]) -> rusqlite::Result<()> {
+    let fts_table = format!("{}_fts", table);
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = ?)",
+        [&fts_table],
+        |row| row.get(0),
+    )?;
+
+    let column_list = columns.join(", ");
+    let new_values = columns.iter().map(|c| format!("new.{}", c)).collect::<Vec<_>>().join(", ");
+    let old_values = columns.iter().map(|c| format!("old.{}", c)).collect::<Vec<_>>().join(", ");
+    let insert = format!(
+        "INSERT INTO {fts} (rowid, {cols}) VALUES (new.id, {new});",
+        fts = fts_table, cols = column_list, new = new_values,
+    );
+    let delete = format!(
+        "INSERT INTO {fts} ({fts}, rowid, {cols}) VALUES ('delete', old.id, {old});",
+        fts = fts_table, cols = column_list, old = old_values,
+    );
+
+    conn.execute_batch(&format!(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS {fts} USING fts5(
+            {cols}, content='{table}', content_rowid='id',
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+        CREATE TRIGGER IF NOT EXISTS {fts}_insert AFTER INSERT ON {table} BEGIN
+            {insert}
+        END;
+        CREATE TRIGGER IF NOT EXISTS {fts}_delete AFTER DELETE ON {table} BEGIN
+            {delete}
+        END;
+        CREATE TRIGGER IF NOT EXISTS {fts}_update AFTER UPDATE OF {cols} ON {table} BEGIN
+            {delete}
+            {insert}
+        END;",
+        fts = fts_table, table = table, cols = column_list, insert = insert, delete = delete,
+    ))?;
 
-    if user_count == 0 {
-        let default_admin = NewUser {
-            username: "AdminSalesys".to_string(),
-            email: "admin@salesys.com".to_string(),
-            password: "admin".to_string(), // En producción usa un hash seguro
-            role: UserRole::Admin,
-            full_name: "Administrador Principal".to_string(),
-        };
-        
-        user_queries::create_user(conn, default_admin)?;
-        println!("Usuario administrador creado por defecto");
+    if !exists {
+        conn.execute(&format!("INSERT INTO {fts} ({fts}) VALUES ('rebuild')", fts = fts_table), [])?;
     }
-    
     Ok(())
 }
+
+// Cada palabra se busca como prefijo y todas deben aparecer: "caf ver"
+// encuentra "Café Veracruz". Las comillas se quitan para no romper la sintaxis.
+pub fn fts_match_expression(text: &str) -> Option<String> {
+    let terms = text
+        .split_whitespace()
+        .map(|term| term.replace('"', ""))
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("\"{}\"*", term))
+        .collect::<Vec<_>>();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
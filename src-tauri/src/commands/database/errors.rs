@@ -0,0 +1,75 @@
+use std::fmt;
+use serde::Serialize;
+
+// Error de reglas de negocio. Viaja dentro de `rusqlite::Error` para que las
+// consultas mantengan su tipo `Result` y el mensaje llegue intacto al frontend.
+#[derive(Debug)]
+pub struct ValidationError(pub String);
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+pub fn validation_error(message: impl Into<String>) -> rusqlite::Error {
+    rusqlite::Error::ToSqlConversionFailure(Box::new(ValidationError(message.into())))
+}
+
+// Falta de existencia al agregar una línea. El frontend recibe los datos
+// estructurados para ofrecer la cantidad disponible.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "code", rename = "insufficient_stock")]
+pub struct InsufficientStock {
+    pub product_id: i32,
+    pub requested: f64,
+    pub available: f64,
+}
+
+impl fmt::Display for InsufficientStock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Existencia insuficiente del producto {}: se solicitaron {} y hay {} disponibles",
+            self.product_id, self.requested, self.available
+        )
+    }
+}
+
+impl std::error::Error for InsufficientStock {}
+
+pub fn insufficient_stock(product_id: i32, requested: f64, available: f64) -> rusqlite::Error {
+    rusqlite::Error::ToSqlConversionFailure(Box::new(InsufficientStock {
+        product_id,
+        requested,
+        available,
+    }))
+}
+
+// Error de los comandos que necesitan distinguir fallas tipadas; el resto
+// sigue llegando al frontend como texto.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum CommandError {
+    InsufficientStock(InsufficientStock),
+    Message(String),
+}
+
+impl From<rusqlite::Error> for CommandError {
+    fn from(error: rusqlite::Error) -> Self {
+        if let rusqlite::Error::ToSqlConversionFailure(inner) = &error {
+            if let Some(stock) = inner.downcast_ref::<InsufficientStock>() {
+                return CommandError::InsufficientStock(stock.clone());
+            }
+        }
+        CommandError::Message(error.to_string())
+    }
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        CommandError::Message(message)
+    }
+}
@@ -1,3 +1,4 @@
 pub mod connection;
+pub mod errors;
 pub mod models;
 pub mod queries;
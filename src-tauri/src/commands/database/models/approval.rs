@@ -0,0 +1,63 @@
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalAction {
+    Discount,
+    PriceChange,
+    LineVoid,
+    SaleCancel,
+}
+
+impl fmt::Display for ApprovalAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApprovalAction::Discount => write!(f, "discount"),
+            ApprovalAction::PriceChange => write!(f, "price_change"),
+            ApprovalAction::LineVoid => write!(f, "line_void"),
+            ApprovalAction::SaleCancel => write!(f, "sale_cancel"),
+        }
+    }
+}
+
+impl From<&str> for ApprovalAction {
+    fn from(value: &str) -> Self {
+        match value {
+            "discount" => ApprovalAction::Discount,
+            "price_change" => ApprovalAction::PriceChange,
+            "line_void" => ApprovalAction::LineVoid,
+            _ => ApprovalAction::SaleCancel,
+        }
+    }
+}
+
+impl ApprovalAction {
+    pub fn description(&self) -> &'static str {
+        match self {
+            ApprovalAction::Discount => "aplicar el descuento",
+            ApprovalAction::PriceChange => "cambiar el precio",
+            ApprovalAction::LineVoid => "eliminar la línea",
+            ApprovalAction::SaleCancel => "cancelar la venta",
+        }
+    }
+}
+
+// Operación concreta que se autoriza: `target_id` es el producto en descuentos
+// y cambios de precio, y el detalle de venta al eliminar una línea. `amount` es
+// el descuento máximo o el precio unitario autorizado.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalRequest {
+    pub action: ApprovalAction,
+    pub sale_id: i32,
+    pub target_id: Option<i32>,
+    pub amount: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SupervisorApproval {
+    pub token: String,
+    pub approved_by: i32,
+    pub supervisor_name: String,
+    pub expires_at: String,
+}
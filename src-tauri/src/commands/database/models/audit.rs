@@ -0,0 +1,23 @@
+use serde::{Serialize, Deserialize};
+use chrono::NaiveDateTime;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: i32,
+    pub user_id: Option<i32>,
+    #[serde(with = "crate::utils::date_format")]
+    pub timestamp: NaiveDateTime,
+    pub action: String,
+    pub entity: String,
+    pub entity_id: Option<i32>,
+    pub changes: serde_json::Value,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AuditLogFilter {
+    pub user_id: Option<i32>,
+    pub entity: Option<String>,
+    pub entity_id: Option<i32>,
+    pub start: Option<String>,
+    pub end: Option<String>,
+}
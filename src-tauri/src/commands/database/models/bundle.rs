@@ -0,0 +1,27 @@
+use serde::{Serialize, Deserialize};
+use super::product::Product;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleComponent {
+    pub component_id: i32,
+    pub name: String,
+    pub code: String,
+    pub unit: String,
+    pub quantity: f64,
+    pub stock: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewBundleComponent {
+    pub component_id: i32,
+    pub quantity: f64,
+}
+
+// Paquete o kit: se vende con su propio precio pero no lleva existencia;
+// `available` son los paquetes que alcanzan a armarse con los componentes.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Bundle {
+    pub product: Product,
+    pub components: Vec<BundleComponent>,
+    pub available: f64,
+}
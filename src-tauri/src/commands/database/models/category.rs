@@ -0,0 +1,34 @@
+use serde::{Serialize, Deserialize};
+
+// Catálogo jerárquico: departamento → categoría → subcategoría. `depth`
+// empieza en 0 para los departamentos y `path` trae los nombres de la raíz
+// a la categoría, p. ej. "Abarrotes > Bebidas > Refrescos".
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Category {
+    pub id: i32,
+    pub parent_id: Option<i32>,
+    pub name: String,
+    pub depth: i32,
+    pub path: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewCategory {
+    pub parent_id: Option<i32>,
+    pub name: String,
+}
+
+// Ventas de una categoría incluyendo todas sus subcategorías, netas de
+// devoluciones. Los productos sin categoría se agrupan en un renglón con
+// `category_id` nulo.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CategorySales {
+    pub category_id: Option<i32>,
+    pub name: String,
+    pub path: String,
+    pub depth: i32,
+    pub sales_count: i64,
+    pub quantity: f64,
+    pub net_sales: f64,
+    pub taxes: f64,
+}
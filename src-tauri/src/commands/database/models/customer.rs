@@ -8,6 +8,8 @@ pub struct Customer {
     pub phone: Option<String>,
     pub address: Option<String>,
     pub rfc: Option<String>,
+    #[serde(default)]
+    pub store_credit: f64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -17,4 +19,18 @@ pub struct NewCustomer {
     pub phone: Option<String>,
     pub address: Option<String>,
     pub rfc: Option<String>,
-}
\ No newline at end of file
+}
+#[derive(Debug, Serialize)]
+pub struct CustomerHighlights {
+    pub name: String,
+    pub rfc: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CustomerMatch {
+    pub customer: Customer,
+    pub highlights: CustomerHighlights,
+    pub rank: f64,
+}
@@ -0,0 +1,88 @@
+use serde::{Serialize, Deserialize};
+use chrono::NaiveDateTime;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MovementType {
+    Initial,
+    Sale,
+    SaleVoid,
+    Return,
+    Purchase,
+    Adjustment,
+    Transfer,
+    Cancellation,
+}
+
+impl MovementType {
+    // Los movimientos de venta, devolución y cancelación los genera el sistema
+    pub fn is_manual(&self) -> bool {
+        matches!(
+            self,
+            MovementType::Purchase | MovementType::Adjustment | MovementType::Transfer
+        )
+    }
+}
+
+impl fmt::Display for MovementType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MovementType::Initial => write!(f, "initial"),
+            MovementType::Sale => write!(f, "sale"),
+            MovementType::SaleVoid => write!(f, "sale_void"),
+            MovementType::Return => write!(f, "return"),
+            MovementType::Purchase => write!(f, "purchase"),
+            MovementType::Adjustment => write!(f, "adjustment"),
+            MovementType::Transfer => write!(f, "transfer"),
+            MovementType::Cancellation => write!(f, "cancellation"),
+        }
+    }
+}
+
+impl From<&str> for MovementType {
+    fn from(value: &str) -> Self {
+        match value {
+            "initial" => MovementType::Initial,
+            "sale" => MovementType::Sale,
+            "sale_void" => MovementType::SaleVoid,
+            "return" => MovementType::Return,
+            "purchase" => MovementType::Purchase,
+            "transfer" => MovementType::Transfer,
+            "cancellation" => MovementType::Cancellation,
+            _ => MovementType::Adjustment,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InventoryMovement {
+    pub id: i32,
+    pub product_id: i32,
+    #[serde(with = "crate::utils::date_format")]
+    pub date: NaiveDateTime,
+    pub movement_type: MovementType,
+    pub quantity: f64,
+    pub stock_after: f64,
+    pub reason: Option<String>,
+    pub user_id: Option<i32>,
+    pub reference_type: Option<String>,
+    pub reference_id: Option<i32>,
+}
+
+// `quantity` lleva signo: positivo para entradas y negativo para salidas
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewInventoryMovement {
+    pub product_id: i32,
+    pub movement_type: MovementType,
+    pub quantity: f64,
+    pub reason: Option<String>,
+    pub reference_type: Option<String>,
+    pub reference_id: Option<i32>,
+}
+
+// Las cantidades fraccionarias se redondean a seis decimales para que las
+// sumas de movimientos no acumulen error de punto flotante
+pub fn round_quantity(quantity: f64) -> f64 {
+    (quantity * 1_000_000.0).round() / 1_000_000.0
+}
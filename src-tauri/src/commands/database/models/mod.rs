@@ -3,4 +3,17 @@ pub mod customer;
 pub mod sale;
 pub mod sale_detail;
 pub mod invoice;
-pub mod user;
\ No newline at end of file
+pub mod user;
+pub mod settings;
+pub mod two_factor;
+pub mod approval;
+pub mod audit;
+pub mod sale_chain;
+pub mod inventory;
+pub mod sale_return;
+pub mod payment;
+pub mod register;
+pub mod report;
+pub mod category;
+pub mod variant;
+pub mod bundle;
\ No newline at end of file
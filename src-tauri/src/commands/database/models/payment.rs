@@ -0,0 +1,88 @@
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use super::sale_chain::SaleSeal;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentMethod {
+    Cash,
+    Credit,
+    Debit,
+    Transfer,
+}
+
+impl PaymentMethod {
+    pub fn is_card(&self) -> bool {
+        matches!(self, PaymentMethod::Credit | PaymentMethod::Debit)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PaymentMethod::Cash => "Efectivo",
+            PaymentMethod::Credit => "Tarjeta de crédito",
+            PaymentMethod::Debit => "Tarjeta de débito",
+            PaymentMethod::Transfer => "Transferencia",
+        }
+    }
+}
+
+impl fmt::Display for PaymentMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaymentMethod::Cash => write!(f, "cash"),
+            PaymentMethod::Credit => write!(f, "credit"),
+            PaymentMethod::Debit => write!(f, "debit"),
+            PaymentMethod::Transfer => write!(f, "transfer"),
+        }
+    }
+}
+
+impl From<&str> for PaymentMethod {
+    fn from(value: &str) -> Self {
+        match value {
+            "credit" => PaymentMethod::Credit,
+            "debit" => PaymentMethod::Debit,
+            "transfer" => PaymentMethod::Transfer,
+            _ => PaymentMethod::Cash,
+        }
+    }
+}
+
+// `amount` es lo que entregó el cliente; en efectivo puede exceder el total
+// y la diferencia queda en `change_amount`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SalePayment {
+    pub id: i32,
+    pub sale_id: i32,
+    pub method: PaymentMethod,
+    pub amount: f64,
+    pub change_amount: f64,
+    pub reference: Option<String>,
+    pub card_last4: Option<String>,
+    pub authorization_code: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewSalePayment {
+    pub method: PaymentMethod,
+    pub amount: f64,
+    pub reference: Option<String>,
+    pub card_last4: Option<String>,
+    pub authorization_code: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SaleCompletion {
+    pub seal: SaleSeal,
+    pub total: f64,
+    pub tendered: f64,
+    pub change_due: f64,
+    pub payments: Vec<SalePayment>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TenderTotal {
+    pub method: PaymentMethod,
+    pub payments: i64,
+    pub amount: f64,
+}
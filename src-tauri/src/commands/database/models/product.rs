@@ -1,12 +1,45 @@
 use serde::{Serialize, Deserialize};
+use std::fmt;
 
+fn default_unit() -> String {
+    "pza".to_string()
+}
+
+// La existencia se guarda en la unidad base (`unit`); `quantity_decimals`
+// indica cuántos decimales admite: 0 para piezas, 3 para kilos.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Product {
     pub id: i32,
     pub name: String,
     pub code: String,
     pub price: f64,
-    pub stock: i32,
+    pub stock: f64,
+    #[serde(default)]
+    pub allow_backorder: bool,
+    pub description: Option<String>,
+    // Código de báscula para artículos pesados en mostrador
+    pub plu: Option<String>,
+    #[serde(default = "default_unit")]
+    pub unit: String,
+    #[serde(default)]
+    pub quantity_decimals: u32,
+    pub category_id: Option<i32>,
+}
+
+impl Product {
+    pub fn validate_quantity(&self, quantity: f64) -> Result<(), String> {
+        if quantity <= 0.0 || !quantity.is_finite() {
+            return Err(format!("La cantidad de {} debe ser mayor a cero", self.name));
+        }
+        let scaled = quantity * 10f64.powi(self.quantity_decimals as i32);
+        if (scaled - scaled.round()).abs() > 1e-6 {
+            return Err(format!(
+                "{} admite a lo más {} decimales en {}",
+                self.name, self.quantity_decimals, self.unit
+            ));
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -14,5 +47,264 @@ pub struct NewProduct {
     pub name: String,
     pub code: String,
     pub price: f64,
-    pub stock: i32,
-}
\ No newline at end of file
+    pub stock: f64,
+    #[serde(default)]
+    pub allow_backorder: bool,
+    pub description: Option<String>,
+    pub plu: Option<String>,
+    #[serde(default = "default_unit")]
+    pub unit: String,
+    #[serde(default)]
+    pub quantity_decimals: u32,
+    pub category_id: Option<i32>,
+}
+
+// Unidad alterna de compra o traspaso: `factor` unidades base por unidad,
+// p. ej. la caja con 12 piezas
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProductUnit {
+    pub id: i32,
+    pub product_id: i32,
+    pub name: String,
+    pub factor: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewProductUnit {
+    pub product_id: i32,
+    pub name: String,
+    pub factor: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BarcodeType {
+    Ean13,
+    UpcA,
+    Code128,
+    Internal,
+}
+
+impl BarcodeType {
+    pub fn validate(&self, barcode: &str) -> Result<(), String> {
+        match self {
+            BarcodeType::Ean13 => validate_gtin(barcode, 13, "EAN-13"),
+            BarcodeType::UpcA => validate_gtin(barcode, 12, "UPC-A"),
+            BarcodeType::Code128 => {
+                if barcode.len() > 48 || !barcode.chars().all(|c| (' '..='~').contains(&c)) {
+                    Err("Un Code128 admite hasta 48 caracteres ASCII imprimibles".to_string())
+                } else {
+                    Ok(())
+                }
+            }
+            BarcodeType::Internal => {
+                if barcode.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                    Ok(())
+                } else {
+                    Err("Un código interno solo admite letras, números y guiones".to_string())
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for BarcodeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BarcodeType::Ean13 => write!(f, "ean13"),
+            BarcodeType::UpcA => write!(f, "upc_a"),
+            BarcodeType::Code128 => write!(f, "code128"),
+            BarcodeType::Internal => write!(f, "internal"),
+        }
+    }
+}
+
+impl From<&str> for BarcodeType {
+    fn from(value: &str) -> Self {
+        match value {
+            "ean13" => BarcodeType::Ean13,
+            "upc_a" => BarcodeType::UpcA,
+            "code128" => BarcodeType::Code128,
+            _ => BarcodeType::Internal,
+        }
+    }
+}
+
+// Dígito verificador GS1 (EAN/UPC): desde la derecha, los dígitos se
+// ponderan 3, 1, 3, 1... sin contar el verificador.
+pub fn gtin_check_digit(digits: &str) -> Option<u32> {
+    let mut sum = 0;
+    for (i, c) in digits.chars().rev().enumerate() {
+        let digit = c.to_digit(10)?;
+        sum += if i % 2 == 0 { digit * 3 } else { digit };
+    }
+    Some((10 - sum % 10) % 10)
+}
+
+fn validate_gtin(barcode: &str, length: usize, label: &str) -> Result<(), String> {
+    if barcode.len() != length || !barcode.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("Un {} debe tener {} dígitos", label, length));
+    }
+    let (digits, check) = barcode.split_at(length - 1);
+    if gtin_check_digit(digits) != check.chars().next().and_then(|c| c.to_digit(10)) {
+        return Err(format!("El dígito verificador del {} {} no es válido", label, barcode));
+    }
+    Ok(())
+}
+
+fn default_pack_quantity() -> i32 {
+    1
+}
+
+// `pack_quantity` indica cuántas piezas representa el código, p. ej. la caja de 12
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProductBarcode {
+    pub id: i32,
+    pub product_id: i32,
+    pub barcode: String,
+    pub barcode_type: BarcodeType,
+    pub pack_quantity: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewProductBarcode {
+    pub product_id: i32,
+    pub barcode: String,
+    pub barcode_type: BarcodeType,
+    #[serde(default = "default_pack_quantity")]
+    pub pack_quantity: i32,
+}
+
+// Resultado de una lectura del escáner. `quantity` es la cantidad que se
+// lleva a la venta: las piezas del empaque o los kilos de la etiqueta de
+// báscula; en etiquetas con precio, `embedded_price` es el importe impreso.
+#[derive(Debug, Serialize)]
+pub struct ScannedProduct {
+    pub product: Product,
+    pub pack_quantity: i32,
+    pub quantity: f64,
+    pub weight: Option<f64>,
+    pub embedded_price: Option<f64>,
+}
+
+impl ScannedProduct {
+    pub fn pack(product: Product, pack_quantity: i32) -> Self {
+        ScannedProduct {
+            product,
+            pack_quantity,
+            quantity: pack_quantity as f64,
+            weight: None,
+            embedded_price: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProductSort {
+    #[default]
+    Name,
+    Code,
+    Price,
+    Stock,
+}
+
+impl ProductSort {
+    pub fn column(&self) -> &'static str {
+        match self {
+            ProductSort::Name => "name COLLATE NOCASE",
+            ProductSort::Code => "code",
+            ProductSort::Price => "price",
+            ProductSort::Stock => "stock",
+        }
+    }
+}
+
+fn default_page() -> u32 {
+    1
+}
+
+fn default_page_size() -> u32 {
+    50
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProductSearch {
+    pub text: Option<String>,
+    // Incluye los productos de las subcategorías
+    pub category_id: Option<i32>,
+    #[serde(default)]
+    pub sort_by: ProductSort,
+    #[serde(default)]
+    pub descending: bool,
+    #[serde(default = "default_page")]
+    pub page: u32,
+    #[serde(default = "default_page_size")]
+    pub page_size: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProductPage {
+    pub items: Vec<Product>,
+    pub total: i64,
+    pub page: u32,
+    pub page_size: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProductHighlights {
+    pub name: String,
+    pub code: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProductMatch {
+    pub product: Product,
+    pub highlights: ProductHighlights,
+    pub rank: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_digit_matches_published_codes() {
+        assert_eq!(gtin_check_digit("400638133393"), Some(1));
+        assert_eq!(gtin_check_digit("03600029145"), Some(2));
+        assert_eq!(gtin_check_digit("9638507"), Some(4));
+        // Suma múltiplo de 10: el dígito es 0, no 10
+        assert_eq!(gtin_check_digit("000000000000"), Some(0));
+        assert_eq!(gtin_check_digit("12345A"), None);
+    }
+
+    #[test]
+    fn ean13_and_upca_require_length_and_check_digit() {
+        assert!(BarcodeType::Ean13.validate("4006381333931").is_ok());
+        assert!(BarcodeType::UpcA.validate("036000291452").is_ok());
+
+        let wrong_check = BarcodeType::Ean13.validate("4006381333932").unwrap_err();
+        assert!(wrong_check.contains("dígito verificador"));
+        let wrong_length = BarcodeType::UpcA.validate("0036000291452").unwrap_err();
+        assert!(wrong_length.contains("12 dígitos"));
+        assert!(BarcodeType::Ean13.validate("40063813339 1").is_err());
+    }
+
+    #[test]
+    fn code128_and_internal_limit_their_characters() {
+        assert!(BarcodeType::Code128.validate("LOTE 2024/05-A").is_ok());
+        assert!(BarcodeType::Code128.validate(&"X".repeat(49)).is_err());
+        assert!(BarcodeType::Code128.validate("CAFÉ").is_err());
+
+        assert!(BarcodeType::Internal.validate("CAJA-12").is_ok());
+        assert!(BarcodeType::Internal.validate("CAJA 12").is_err());
+    }
+
+    #[test]
+    fn barcode_type_round_trips_through_its_column_value() {
+        for barcode_type in [BarcodeType::Ean13, BarcodeType::UpcA, BarcodeType::Code128, BarcodeType::Internal] {
+            assert_eq!(BarcodeType::from(barcode_type.to_string().as_str()), barcode_type);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CashRegister {
+    pub id: i32,
+    pub name: String,
+    pub active: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewCashRegister {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DenominationCount {
+    pub value: f64,
+    pub count: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegisterSession {
+    pub id: i32,
+    pub register_id: i32,
+    pub user_id: i32,
+    pub opened_at: String,
+    pub opening_float: f64,
+    pub closed_at: Option<String>,
+    pub closed_by: Option<i32>,
+    pub expected_cash: Option<f64>,
+    pub counted_cash: Option<f64>,
+    pub over_short: Option<f64>,
+    pub denominations: Option<Vec<DenominationCount>>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OpenRegisterSession {
+    pub register_id: i32,
+    pub opening_float: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CloseRegisterSession {
+    pub denominations: Vec<DenominationCount>,
+    pub notes: Option<String>,
+}
+
+// Entradas y salidas de efectivo del cajón que no son ventas
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CashMovementType {
+    CashIn,
+    Payout,
+    SafeDrop,
+}
+
+impl CashMovementType {
+    pub fn sign(&self) -> f64 {
+        match self {
+            CashMovementType::CashIn => 1.0,
+            CashMovementType::Payout | CashMovementType::SafeDrop => -1.0,
+        }
+    }
+}
+
+impl fmt::Display for CashMovementType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CashMovementType::CashIn => write!(f, "cash_in"),
+            CashMovementType::Payout => write!(f, "payout"),
+            CashMovementType::SafeDrop => write!(f, "safe_drop"),
+        }
+    }
+}
+
+impl From<&str> for CashMovementType {
+    fn from(value: &str) -> Self {
+        match value {
+            "payout" => CashMovementType::Payout,
+            "safe_drop" => CashMovementType::SafeDrop,
+            _ => CashMovementType::CashIn,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CashMovement {
+    pub id: i32,
+    pub session_id: i32,
+    pub movement_type: CashMovementType,
+    pub amount: f64,
+    pub reason: String,
+    pub user_id: i32,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewCashMovement {
+    pub movement_type: CashMovementType,
+    pub amount: f64,
+    pub reason: String,
+}
@@ -0,0 +1,40 @@
+use serde::{Serialize, Deserialize};
+use super::payment::TenderTotal;
+
+// Resumen de una caja en un día. El corte X se calcula al momento y no se
+// guarda; el corte Z lleva folio consecutivo y cierra el día.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegisterReport {
+    pub register_id: i32,
+    pub business_date: String,
+    pub z_number: Option<i64>,
+    pub generated_at: String,
+    pub sales_count: i64,
+    pub gross_sales: f64,
+    pub discounts: f64,
+    pub net_sales: f64,
+    pub taxes: f64,
+    pub total: f64,
+    pub canceled_count: i64,
+    pub canceled_total: f64,
+    pub returns_count: i64,
+    pub returns_total: f64,
+    pub tenders: Vec<TenderTotal>,
+    #[serde(default)]
+    pub cash_in: f64,
+    #[serde(default)]
+    pub payouts: f64,
+    #[serde(default)]
+    pub safe_drops: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ZReportSummary {
+    pub id: i32,
+    pub register_id: i32,
+    pub business_date: String,
+    pub z_number: i64,
+    pub created_by: i32,
+    pub created_at: String,
+    pub total: f64,
+}
@@ -1,5 +1,51 @@
 use serde::{Serialize, Deserialize};
 use chrono::NaiveDateTime;
+use std::fmt;
+
+// Ciclo de vida: draft → completed → canceled/refunded. Un borrador también
+// puede cancelarse antes de cobrarse.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SaleStatus {
+    Draft,
+    Completed,
+    Canceled,
+    Refunded,
+}
+
+impl SaleStatus {
+    pub fn can_transition_to(&self, next: SaleStatus) -> bool {
+        matches!(
+            (self, next),
+            (SaleStatus::Draft, SaleStatus::Completed)
+                | (SaleStatus::Draft, SaleStatus::Canceled)
+                | (SaleStatus::Completed, SaleStatus::Canceled)
+                | (SaleStatus::Completed, SaleStatus::Refunded)
+        )
+    }
+}
+
+impl fmt::Display for SaleStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaleStatus::Draft => write!(f, "draft"),
+            SaleStatus::Completed => write!(f, "completed"),
+            SaleStatus::Canceled => write!(f, "canceled"),
+            SaleStatus::Refunded => write!(f, "refunded"),
+        }
+    }
+}
+
+impl From<&str> for SaleStatus {
+    fn from(value: &str) -> Self {
+        match value {
+            "completed" => SaleStatus::Completed,
+            "canceled" => SaleStatus::Canceled,
+            "refunded" => SaleStatus::Refunded,
+            _ => SaleStatus::Draft,
+        }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Sale {
@@ -12,6 +58,9 @@ pub struct Sale {
     pub total: f64,
     pub payment_method: String,
     pub status: String,
+    pub cancel_reason: Option<String>,
+    pub canceled_by: Option<i32>,
+    pub canceled_at: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -21,5 +70,49 @@ pub struct NewSale {
     pub taxes: f64,
     pub total: f64,
     pub payment_method: String,
-    pub status: Option<String>,
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [SaleStatus; 4] = [SaleStatus::Draft, SaleStatus::Completed, SaleStatus::Canceled, SaleStatus::Refunded];
+
+    #[test]
+    fn only_lifecycle_transitions_are_allowed() {
+        let allowed = [
+            (SaleStatus::Draft, SaleStatus::Completed),
+            (SaleStatus::Draft, SaleStatus::Canceled),
+            (SaleStatus::Completed, SaleStatus::Canceled),
+            (SaleStatus::Completed, SaleStatus::Refunded),
+        ];
+        for current in ALL {
+            for next in ALL {
+                assert_eq!(
+                    current.can_transition_to(next),
+                    allowed.contains(&(current, next)),
+                    "{} → {}",
+                    current,
+                    next
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn closed_sales_never_return_to_draft() {
+        for current in ALL {
+            assert!(!current.can_transition_to(SaleStatus::Draft));
+        }
+        assert!(!SaleStatus::Canceled.can_transition_to(SaleStatus::Refunded));
+        assert!(!SaleStatus::Refunded.can_transition_to(SaleStatus::Canceled));
+    }
+
+    #[test]
+    fn status_round_trips_through_its_column_value() {
+        for status in ALL {
+            assert_eq!(SaleStatus::from(status.to_string().as_str()), status);
+        }
+        // Un valor desconocido se lee como borrador
+        assert_eq!(SaleStatus::from("pending"), SaleStatus::Draft);
+    }
+}
@@ -0,0 +1,19 @@
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SaleSeal {
+    pub sale_id: i32,
+    pub chain_seq: i64,
+    pub previous_hash: String,
+    pub hash: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChainVerification {
+    pub valid: bool,
+    pub sealed_sales: i64,
+    // Ventas cerradas antes de habilitar la cadena
+    pub unsealed_sales: i64,
+    pub first_broken_sale_id: Option<i32>,
+    pub reason: Option<String>,
+}
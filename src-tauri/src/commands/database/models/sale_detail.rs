@@ -5,7 +5,7 @@ pub struct SaleDetail {
     pub id: i32,
     pub sale_id: i32,
     pub product_id: i32,
-    pub quantity: i32,
+    pub quantity: f64,
     pub unit_price: f64,
     pub subtotal: f64,
     pub discount: f64,
@@ -16,7 +16,7 @@ pub struct SaleDetail {
 pub struct NewSaleDetail {
     pub sale_id: i32,
     pub product_id: i32,
-    pub quantity: i32,
+    pub quantity: f64,
     pub unit_price: f64,
     pub discount: f64,
     pub tax_percentage: f64,
@@ -29,7 +29,8 @@ pub struct SaleDetailWithProduct {
     pub product_id: i32,
     pub product_name: String,
     pub product_code: String,
-    pub quantity: i32,
+    pub unit: String,
+    pub quantity: f64,
     pub unit_price: f64,
     pub subtotal: f64,
     pub discount: f64,
@@ -0,0 +1,74 @@
+use serde::{Serialize, Deserialize};
+use chrono::NaiveDateTime;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefundMethod {
+    Cash,
+    CardReversal,
+    StoreCredit,
+}
+
+impl fmt::Display for RefundMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RefundMethod::Cash => write!(f, "cash"),
+            RefundMethod::CardReversal => write!(f, "card_reversal"),
+            RefundMethod::StoreCredit => write!(f, "store_credit"),
+        }
+    }
+}
+
+impl From<&str> for RefundMethod {
+    fn from(value: &str) -> Self {
+        match value {
+            "card_reversal" => RefundMethod::CardReversal,
+            "store_credit" => RefundMethod::StoreCredit,
+            _ => RefundMethod::Cash,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReturnItem {
+    pub id: i32,
+    pub return_id: i32,
+    pub sale_detail_id: i32,
+    pub product_id: i32,
+    pub quantity: f64,
+    pub subtotal: f64,
+    pub tax_amount: f64,
+    pub restocked: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SaleReturn {
+    pub id: i32,
+    pub sale_id: i32,
+    #[serde(with = "crate::utils::date_format")]
+    pub date: NaiveDateTime,
+    pub refund_method: RefundMethod,
+    pub reason: String,
+    pub subtotal: f64,
+    pub taxes: f64,
+    pub total: f64,
+    pub user_id: i32,
+    pub items: Vec<ReturnItem>,
+}
+
+// `restock` en falso indica mercancía dañada que no vuelve al inventario
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewReturnItem {
+    pub sale_detail_id: i32,
+    pub quantity: f64,
+    pub restock: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewSaleReturn {
+    pub sale_id: i32,
+    pub refund_method: RefundMethod,
+    pub reason: String,
+    pub items: Vec<NewReturnItem>,
+}
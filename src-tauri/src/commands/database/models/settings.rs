@@ -0,0 +1,307 @@
+use serde::{Serialize, Deserialize};
+use super::user::UserRole;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BusinessSettings {
+    pub business_name: String,
+    pub rfc: Option<String>,
+    pub address: Option<String>,
+    pub phone: Option<String>,
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaxSettings {
+    pub tax_percentage: f64,
+    pub prices_include_tax: bool,
+}
+
+impl Default for TaxSettings {
+    fn default() -> Self {
+        TaxSettings {
+            tax_percentage: 16.0,
+            prices_include_tax: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetupStatus {
+    pub provisioned: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetupAdmin {
+    pub username: String,
+    pub email: String,
+    pub password: String,
+    pub full_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SystemSetup {
+    pub admin: SetupAdmin,
+    pub business: BusinessSettings,
+    pub tax: TaxSettings,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub require_uppercase: bool,
+    pub require_lowercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    pub history_size: u32,
+    pub max_age_days: Option<u32>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            min_length: 8,
+            require_uppercase: true,
+            require_lowercase: true,
+            require_digit: true,
+            require_symbol: false,
+            history_size: 5,
+            max_age_days: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TwoFactorPolicy {
+    pub required_roles: Vec<UserRole>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PosRestrictions {
+    pub max_discount_percentage: f64,
+    pub approve_price_changes: bool,
+    pub approve_line_voids: bool,
+    pub approve_sale_cancellations: bool,
+}
+
+impl Default for PosRestrictions {
+    fn default() -> Self {
+        PosRestrictions {
+            max_discount_percentage: 10.0,
+            approve_price_changes: true,
+            approve_line_voids: true,
+            approve_sale_cancellations: true,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct InventorySettings {
+    pub allow_backorders: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaperWidth {
+    Mm58,
+    Mm80,
+}
+
+impl PaperWidth {
+    // Caracteres por renglón con la fuente A
+    pub fn columns(&self) -> usize {
+        match self {
+            PaperWidth::Mm58 => 32,
+            PaperWidth::Mm80 => 48,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PrinterConnection {
+    Device { path: String },
+    File { path: String },
+    Network { host: String, port: u16 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrinterSettings {
+    pub enabled: bool,
+    pub paper_width: PaperWidth,
+    pub connection: PrinterConnection,
+    pub footer: Option<String>,
+    // Portal de autofacturación; si existe se imprime un QR con folio y total
+    pub self_invoice_url: Option<String>,
+    pub print_barcode: bool,
+}
+
+impl Default for PrinterSettings {
+    fn default() -> Self {
+        PrinterSettings {
+            enabled: false,
+            paper_width: PaperWidth::Mm80,
+            connection: PrinterConnection::Device {
+                path: "/dev/usb/lp0".to_string(),
+            },
+            footer: Some("¡Gracias por su compra!".to_string()),
+            self_invoice_url: None,
+            print_barcode: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScaleValueKind {
+    Weight,
+    Price,
+}
+
+// Etiqueta EAN-13 de báscula: prefijo, PLU, valor y dígito verificador. El
+// valor ocupa los dígitos restantes y se divide entre 10^decimals (gramos
+// a kilos con 3, centavos a pesos con 2).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScaleBarcodeRule {
+    pub prefix: String,
+    pub plu_length: usize,
+    pub value_kind: ScaleValueKind,
+    pub value_decimals: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScaleReading {
+    pub plu: String,
+    pub value_kind: ScaleValueKind,
+    pub value: f64,
+}
+
+impl ScaleBarcodeRule {
+    fn value_length(&self) -> usize {
+        12usize.saturating_sub(self.prefix.len() + self.plu_length)
+    }
+
+    // `barcode` ya debe ser un EAN-13 con dígito verificador válido
+    pub fn parse(&self, barcode: &str) -> Option<ScaleReading> {
+        if !barcode.starts_with(&self.prefix) {
+            return None;
+        }
+        let plu_start = self.prefix.len();
+        let value_start = plu_start + self.plu_length;
+        let raw_value: u64 = barcode[value_start..12].parse().ok()?;
+        Some(ScaleReading {
+            plu: barcode[plu_start..value_start].trim_start_matches('0').to_string(),
+            value_kind: self.value_kind,
+            value: raw_value as f64 / 10f64.powi(self.value_decimals as i32),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScaleBarcodeSettings {
+    pub rules: Vec<ScaleBarcodeRule>,
+}
+
+impl ScaleBarcodeSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        for rule in &self.rules {
+            if rule.prefix.is_empty() || !rule.prefix.chars().all(|c| c.is_ascii_digit()) {
+                return Err(format!("El prefijo '{}' debe contener solo dígitos", rule.prefix));
+            }
+            if rule.plu_length == 0 || rule.value_length() < 4 {
+                return Err(format!(
+                    "El prefijo {} no deja espacio para el PLU y un valor de al menos 4 dígitos",
+                    rule.prefix
+                ));
+            }
+            if rule.value_decimals > 3 {
+                return Err("El valor admite a lo más 3 decimales".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    // Con prefijos traslapados ("2" y "22") gana el más largo
+    pub fn parse(&self, barcode: &str) -> Option<ScaleReading> {
+        self.rules
+            .iter()
+            .filter(|rule| barcode.starts_with(&rule.prefix))
+            .max_by_key(|rule| rule.prefix.len())
+            .and_then(|rule| rule.parse(barcode))
+    }
+}
+
+impl Default for ScaleBarcodeSettings {
+    fn default() -> Self {
+        ScaleBarcodeSettings {
+            rules: vec![
+                ScaleBarcodeRule {
+                    prefix: "20".to_string(),
+                    plu_length: 5,
+                    value_kind: ScaleValueKind::Weight,
+                    value_decimals: 3,
+                },
+                ScaleBarcodeRule {
+                    prefix: "22".to_string(),
+                    plu_length: 5,
+                    value_kind: ScaleValueKind::Price,
+                    value_decimals: 2,
+                },
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::product::gtin_check_digit;
+
+    fn ean13(digits: &str) -> String {
+        format!("{}{}", digits, gtin_check_digit(digits).unwrap())
+    }
+
+    #[test]
+    fn default_rules_read_weight_and_price_labels() {
+        let settings = ScaleBarcodeSettings::default();
+        assert_eq!(
+            settings.parse(&ean13("200012301250")),
+            Some(ScaleReading { plu: "123".to_string(), value_kind: ScaleValueKind::Weight, value: 1.25 })
+        );
+        assert_eq!(
+            settings.parse(&ean13("220045604599")),
+            Some(ScaleReading { plu: "456".to_string(), value_kind: ScaleValueKind::Price, value: 45.99 })
+        );
+        assert_eq!(settings.parse(&ean13("210012301250")), None);
+        assert_eq!(settings.parse(&ean13("750103131130")), None);
+    }
+
+    #[test]
+    fn longest_prefix_wins() {
+        let settings = ScaleBarcodeSettings {
+            rules: vec![
+                ScaleBarcodeRule { prefix: "2".to_string(), plu_length: 6, value_kind: ScaleValueKind::Weight, value_decimals: 3 },
+                ScaleBarcodeRule { prefix: "29".to_string(), plu_length: 4, value_kind: ScaleValueKind::Price, value_decimals: 2 },
+            ],
+        };
+        assert!(settings.validate().is_ok());
+
+        let weight = settings.parse(&ean13("212345601500")).unwrap();
+        assert_eq!((weight.plu.as_str(), weight.value_kind, weight.value), ("123456", ScaleValueKind::Weight, 1.5));
+        let price = settings.parse(&ean13("291234012345")).unwrap();
+        assert_eq!((price.plu.as_str(), price.value_kind, price.value), ("1234", ScaleValueKind::Price, 123.45));
+    }
+
+    #[test]
+    fn validate_rejects_rules_without_room_for_a_value() {
+        let rule = |prefix: &str, plu_length: usize, value_decimals: u32| ScaleBarcodeSettings {
+            rules: vec![ScaleBarcodeRule { prefix: prefix.to_string(), plu_length, value_kind: ScaleValueKind::Weight, value_decimals }],
+        };
+        assert!(ScaleBarcodeSettings::default().validate().is_ok());
+        assert!(rule("", 5, 3).validate().is_err());
+        assert!(rule("2A", 5, 3).validate().is_err());
+        assert!(rule("20", 0, 3).validate().is_err());
+        assert!(rule("20", 7, 3).validate().is_err());
+        assert!(rule("20", 6, 3).validate().is_ok());
+        assert!(rule("20", 5, 4).validate().is_err());
+    }
+}
@@ -1,13 +1,20 @@
 use serde::{Serialize, Deserialize};
 use std::fmt;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum UserRole {
     Admin,
     Seller,
     Manager,
 }
 
+impl UserRole {
+    // Roles que pueden autorizar operaciones restringidas en el punto de venta
+    pub fn is_supervisor(&self) -> bool {
+        matches!(self, UserRole::Admin | UserRole::Manager)
+    }
+}
+
 impl fmt::Display for UserRole {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -38,6 +45,8 @@ pub struct User {
     pub role: UserRole,
     pub full_name: String,
     pub is_active: bool,
+    #[serde(default)]
+    pub must_change_password: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -47,4 +56,13 @@ pub struct NewUser {
     pub password: String,
     pub role: UserRole,
     pub full_name: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum AuthResult {
+    Authenticated { user: User },
+    TwoFactorRequired { challenge_token: String },
+    TwoFactorSetupRequired { user: User },
+    Failed,
 }
\ No newline at end of file
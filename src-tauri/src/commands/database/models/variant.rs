@@ -0,0 +1,70 @@
+use serde::{Serialize, Deserialize};
+use super::product::Product;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AttributeValue {
+    pub id: i32,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TemplateAttribute {
+    pub id: i32,
+    pub name: String,
+    pub values: Vec<AttributeValue>,
+}
+
+// Modelo del que se generan las variantes (talla, color...). Cada variante es
+// un producto con código, existencia y, opcionalmente, precio propios.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProductTemplate {
+    pub id: i32,
+    pub name: String,
+    pub base_code: String,
+    pub base_price: f64,
+    pub description: Option<String>,
+    pub category_id: Option<i32>,
+    pub unit: String,
+    pub quantity_decimals: u32,
+    pub attributes: Vec<TemplateAttribute>,
+    pub variant_count: i64,
+    pub total_stock: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewTemplateAttribute {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewProductTemplate {
+    pub name: String,
+    pub base_code: String,
+    pub base_price: f64,
+    pub description: Option<String>,
+    pub category_id: Option<i32>,
+    pub unit: String,
+    #[serde(default)]
+    pub quantity_decimals: u32,
+    pub attributes: Vec<NewTemplateAttribute>,
+}
+
+// Sin `price_override` la variante toma el precio base del modelo
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProductVariant {
+    pub product: Product,
+    pub template_id: i32,
+    pub price_override: Option<f64>,
+    pub values: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TemplateSales {
+    pub template_id: i32,
+    pub name: String,
+    pub sales_count: i64,
+    pub quantity: f64,
+    pub net_sales: f64,
+    pub taxes: f64,
+}
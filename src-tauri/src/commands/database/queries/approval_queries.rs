@@ -0,0 +1,212 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use super::super::errors::validation_error;
+use super::super::models::approval::{ApprovalAction, ApprovalRequest, SupervisorApproval};
+use super::super::models::sale_detail::NewSaleDetail;
+use super::super::models::user::User;
+use super::{product_queries, settings_queries, user_queries};
+
+const AMOUNT_TOLERANCE: f64 = 0.005;
+
+pub fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS supervisor_approvals (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            token TEXT UNIQUE NOT NULL,
+            action TEXT NOT NULL CHECK(action IN ('discount', 'price_change', 'line_void', 'sale_cancel')),
+            sale_id INTEGER NOT NULL,
+            target_id INTEGER,
+            amount REAL,
+            requested_by INTEGER NOT NULL,
+            approved_by INTEGER NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            expires_at DATETIME NOT NULL,
+            used_at DATETIME,
+            FOREIGN KEY (requested_by) REFERENCES users(id),
+            FOREIGN KEY (approved_by) REFERENCES users(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// El supervisor captura su PIN en la sesión del vendedor; el token resultante
+// solo sirve una vez, para esa operación y dentro de los siguientes minutos.
+pub fn grant(
+    conn: &Connection,
+    requested_by: i32,
+    supervisor_username: &str,
+    credential: &str,
+    request: &ApprovalRequest,
+) -> Result<SupervisorApproval> {
+    let supervisor = user_queries::verify_supervisor(conn, supervisor_username, credential)?
+        .ok_or_else(|| validation_error("Credenciales de supervisor inválidas"))?;
+    if supervisor.id == requested_by {
+        return Err(validation_error("Un supervisor no necesita autorizar sus propias operaciones"));
+    }
+
+    let token = uuid::Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO supervisor_approvals
+        (token, action, sale_id, target_id, amount, requested_by, approved_by, expires_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, datetime('now', '+5 minutes'))",
+        params![
+            token,
+            request.action.to_string(),
+            request.sale_id,
+            request.target_id,
+            request.amount,
+            requested_by,
+            supervisor.id
+        ],
+    )?;
+    let expires_at = conn.query_row(
+        "SELECT expires_at FROM supervisor_approvals WHERE token = ?",
+        [&token],
+        |row| row.get(0),
+    )?;
+
+    Ok(SupervisorApproval {
+        token,
+        approved_by: supervisor.id,
+        supervisor_name: supervisor.full_name,
+        expires_at,
+    })
+}
+
+pub fn required_for_line(conn: &Connection, detail: &NewSaleDetail) -> Result<Vec<ApprovalRequest>> {
+    let restrictions = settings_queries::get_pos_restrictions(conn)?;
+    let product = product_queries::get_product(conn, detail.product_id)?;
+    let mut required = Vec::new();
+
+    let gross = detail.unit_price * detail.quantity;
+    if gross > 0.0 && detail.discount / gross * 100.0 > restrictions.max_discount_percentage {
+        required.push(ApprovalRequest {
+            action: ApprovalAction::Discount,
+            sale_id: detail.sale_id,
+            target_id: Some(detail.product_id),
+            amount: Some(detail.discount),
+        });
+    }
+
+    if restrictions.approve_price_changes && (detail.unit_price - product.price).abs() > AMOUNT_TOLERANCE {
+        required.push(ApprovalRequest {
+            action: ApprovalAction::PriceChange,
+            sale_id: detail.sale_id,
+            target_id: Some(detail.product_id),
+            amount: Some(detail.unit_price),
+        });
+    }
+
+    Ok(required)
+}
+
+pub fn required_for_void(conn: &Connection, detail_id: i32) -> Result<Vec<ApprovalRequest>> {
+    if !settings_queries::get_pos_restrictions(conn)?.approve_line_voids {
+        return Ok(Vec::new());
+    }
+    let sale_id = conn.query_row(
+        "SELECT sale_id FROM sale_details WHERE id = ?",
+        [detail_id],
+        |row| row.get(0),
+    )?;
+    Ok(vec![ApprovalRequest {
+        action: ApprovalAction::LineVoid,
+        sale_id,
+        target_id: Some(detail_id),
+        amount: None,
+    }])
+}
+
+pub fn required_for_cancel(conn: &Connection, sale_id: i32) -> Result<Vec<ApprovalRequest>> {
+    if !settings_queries::get_pos_restrictions(conn)?.approve_sale_cancellations {
+        return Ok(Vec::new());
+    }
+    Ok(vec![ApprovalRequest {
+        action: ApprovalAction::SaleCancel,
+        sale_id,
+        target_id: None,
+        amount: None,
+    }])
+}
+
+fn covers(approved: &ApprovalRequest, request: &ApprovalRequest) -> bool {
+    if approved.action != request.action
+        || approved.sale_id != request.sale_id
+        || approved.target_id != request.target_id
+    {
+        return false;
+    }
+
+    match (request.action, approved.amount, request.amount) {
+        // Un descuento autorizado cubre cualquier descuento menor o igual
+        (ApprovalAction::Discount, Some(max), Some(used)) => used <= max + AMOUNT_TOLERANCE,
+        (ApprovalAction::PriceChange, Some(price), Some(used)) => (price - used).abs() <= AMOUNT_TOLERANCE,
+        (ApprovalAction::Discount, _, _) | (ApprovalAction::PriceChange, _, _) => false,
+        _ => true,
+    }
+}
+
+fn consume(conn: &Connection, token: &str, requested_by: i32, request: &ApprovalRequest) -> Result<bool> {
+    let row = conn
+        .query_row(
+            "SELECT id, action, sale_id, target_id, amount FROM supervisor_approvals
+            WHERE token = ? AND requested_by = ? AND used_at IS NULL
+            AND expires_at >= CURRENT_TIMESTAMP",
+            params![token, requested_by],
+            |row| {
+                let action: String = row.get(1)?;
+                Ok((row.get::<_, i32>(0)?, action, row.get(2)?, row.get(3)?, row.get(4)?))
+            },
+        )
+        .optional()?;
+
+    let (id, action, sale_id, target_id, amount) = match row {
+        Some(row) => row,
+        None => return Ok(false),
+    };
+    let approved = ApprovalRequest {
+        action: ApprovalAction::from(action.as_str()),
+        sale_id,
+        target_id,
+        amount,
+    };
+    if !covers(&approved, request) {
+        return Ok(false);
+    }
+
+    conn.execute(
+        "UPDATE supervisor_approvals SET used_at = CURRENT_TIMESTAMP WHERE id = ?",
+        [id],
+    )?;
+    Ok(true)
+}
+
+// Verifica que cada operación restringida tenga su token de autorización y
+// los marca como usados. Los supervisores no necesitan autorización.
+pub fn authorize(
+    conn: &Connection,
+    actor: &User,
+    requests: &[ApprovalRequest],
+    tokens: &[String],
+) -> Result<()> {
+    if actor.role.is_supervisor() {
+        return Ok(());
+    }
+
+    for request in requests {
+        let mut authorized = false;
+        for token in tokens {
+            if consume(conn, token, actor.id, request)? {
+                authorized = true;
+                break;
+            }
+        }
+        if !authorized {
+            return Err(validation_error(format!(
+                "Se requiere autorización de un supervisor para {}",
+                request.action.description()
+            )));
+        }
+    }
+    Ok(())
+}
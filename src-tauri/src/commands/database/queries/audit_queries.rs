@@ -0,0 +1,166 @@
+use rusqlite::{params, params_from_iter, Connection, Result};
+use rusqlite::types::Value as SqlValue;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use super::super::models::audit::{AuditEntry, AuditLogFilter};
+
+// Campos que nunca deben quedar en la bitácora
+const REDACTED_FIELDS: &[&str] = &["password", "password_hash", "pin_hash", "secret"];
+
+pub fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id INTEGER,
+            timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+            action TEXT NOT NULL,
+            entity TEXT NOT NULL,
+            entity_id INTEGER,
+            changes TEXT NOT NULL DEFAULT '{}'
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_audit_log_entity ON audit_log(entity, entity_id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_audit_log_user ON audit_log(user_id, timestamp)",
+        [],
+    )?;
+
+    // La bitácora es de solo inserción
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS audit_log_no_update
+        BEFORE UPDATE ON audit_log
+        BEGIN
+            SELECT RAISE(ABORT, 'La bitácora de auditoría no se puede modificar');
+        END",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS audit_log_no_delete
+        BEFORE DELETE ON audit_log
+        BEGIN
+            SELECT RAISE(ABORT, 'La bitácora de auditoría no se puede modificar');
+        END",
+        [],
+    )?;
+    Ok(())
+}
+
+fn to_object<T: Serialize>(value: Option<&T>) -> Result<Map<String, Value>> {
+    let value = match value {
+        Some(value) => serde_json::to_value(value)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?,
+        None => return Ok(Map::new()),
+    };
+    let mut object = match value {
+        Value::Object(object) => object,
+        other => {
+            let mut object = Map::new();
+            object.insert("value".to_string(), other);
+            object
+        }
+    };
+    object.retain(|key, _| !REDACTED_FIELDS.contains(&key.as_str()));
+    Ok(object)
+}
+
+// Solo se guardan los campos que cambiaron, como `{campo: {before, after}}`.
+// En altas `before` es nulo y en bajas lo es `after`.
+fn diff(before: &Map<String, Value>, after: &Map<String, Value>) -> Value {
+    let mut changes = Map::new();
+    for key in before.keys().chain(after.keys()) {
+        if changes.contains_key(key) {
+            continue;
+        }
+        let old = before.get(key).cloned().unwrap_or(Value::Null);
+        let new = after.get(key).cloned().unwrap_or(Value::Null);
+        if old != new {
+            changes.insert(key.clone(), json!({ "before": old, "after": new }));
+        }
+    }
+    Value::Object(changes)
+}
+
+pub fn record<T: Serialize>(
+    conn: &Connection,
+    user_id: Option<i32>,
+    action: &str,
+    entity: &str,
+    entity_id: Option<i32>,
+    before: Option<&T>,
+    after: Option<&T>,
+) -> Result<()> {
+    let changes = diff(&to_object(before)?, &to_object(after)?);
+    conn.execute(
+        "INSERT INTO audit_log (user_id, action, entity, entity_id, changes)
+        VALUES (?, ?, ?, ?, ?)",
+        params![user_id, action, entity, entity_id, changes.to_string()],
+    )?;
+    Ok(())
+}
+
+// Registro de acciones sin estado antes/después (cambios de contraseña,
+// activación de 2FA, etc.)
+pub fn record_action(
+    conn: &Connection,
+    user_id: Option<i32>,
+    action: &str,
+    entity: &str,
+    entity_id: Option<i32>,
+) -> Result<()> {
+    record::<Value>(conn, user_id, action, entity, entity_id, None, None)
+}
+
+pub fn get_audit_log(conn: &Connection, filter: &AuditLogFilter) -> Result<Vec<AuditEntry>> {
+    let mut sql = String::from(
+        "SELECT id, user_id, timestamp, action, entity, entity_id, changes
+        FROM audit_log WHERE 1 = 1"
+    );
+    let mut values: Vec<SqlValue> = Vec::new();
+
+    if let Some(user_id) = filter.user_id {
+        sql.push_str(" AND user_id = ?");
+        values.push(SqlValue::Integer(user_id as i64));
+    }
+    if let Some(entity) = &filter.entity {
+        sql.push_str(" AND entity = ?");
+        values.push(SqlValue::Text(entity.clone()));
+    }
+    if let Some(entity_id) = filter.entity_id {
+        sql.push_str(" AND entity_id = ?");
+        values.push(SqlValue::Integer(entity_id as i64));
+    }
+    if let Some(start) = &filter.start {
+        sql.push_str(" AND timestamp >= ?");
+        values.push(SqlValue::Text(start.clone()));
+    }
+    if let Some(end) = &filter.end {
+        sql.push_str(" AND timestamp <= ?");
+        values.push(SqlValue::Text(end.clone()));
+    }
+    sql.push_str(" ORDER BY id DESC");
+
+    let mut stmt = conn.prepare(&sql)?;
+    let entries = stmt.query_map(params_from_iter(values), |row| {
+        let timestamp_str: String = row.get(2)?;
+        let changes_str: String = row.get(6)?;
+        Ok(AuditEntry {
+            id: row.get(0)?,
+            user_id: row.get(1)?,
+            timestamp: NaiveDateTime::parse_from_str(&timestamp_str, "%Y-%m-%d %H:%M:%S")
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, Box::new(e)))?,
+            action: row.get(3)?,
+            entity: row.get(4)?,
+            entity_id: row.get(5)?,
+            changes: serde_json::from_str(&changes_str)
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(6, rusqlite::types::Type::Text, Box::new(e)))?,
+        })
+    })?
+    .collect::<Result<Vec<_>>>()?;
+
+    Ok(entries)
+}
@@ -0,0 +1,394 @@
+use std::collections::HashSet;
+use rusqlite::{params, Connection, Result};
+use super::super::errors::validation_error;
+use super::super::models::bundle::{Bundle, BundleComponent, NewBundleComponent};
+use super::super::models::inventory::{round_quantity, NewInventoryMovement};
+use super::{inventory_queries, product_queries};
+
+pub fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS bundle_components (
+            bundle_id INTEGER NOT NULL,
+            component_id INTEGER NOT NULL,
+            quantity REAL NOT NULL CHECK(quantity > 0),
+            PRIMARY KEY (bundle_id, component_id),
+            FOREIGN KEY (bundle_id) REFERENCES products(id) ON DELETE CASCADE,
+            FOREIGN KEY (component_id) REFERENCES products(id) ON DELETE RESTRICT
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_bundle_components_component ON bundle_components(component_id)",
+        [],
+    )?;
+
+    // Componentes que consumió cada línea de venta de un paquete, por unidad
+    // vendida; cancelaciones y devoluciones regresan esto aunque la receta
+    // del paquete cambie después
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = 'sale_detail_components')",
+        [],
+        |row| row.get(0),
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS sale_detail_components (
+            sale_detail_id INTEGER NOT NULL,
+            component_id INTEGER NOT NULL,
+            quantity REAL NOT NULL CHECK(quantity > 0),
+            PRIMARY KEY (sale_detail_id, component_id),
+            FOREIGN KEY (sale_detail_id) REFERENCES sale_details(id) ON DELETE CASCADE,
+            FOREIGN KEY (component_id) REFERENCES products(id)
+        )",
+        [],
+    )?;
+    // Las líneas vendidas antes de existir la tabla toman la receta actual
+    if !exists {
+        conn.execute(
+            "INSERT INTO sale_detail_components (sale_detail_id, component_id, quantity)
+            SELECT sd.id, bc.component_id, bc.quantity
+            FROM sale_details sd JOIN bundle_components bc ON bc.bundle_id = sd.product_id",
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+pub fn is_bundle(conn: &Connection, product_id: i32) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM bundle_components WHERE bundle_id = ?)",
+        [product_id],
+        |row| row.get(0),
+    )
+}
+
+fn is_component(conn: &Connection, product_id: i32) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM bundle_components WHERE component_id = ?)",
+        [product_id],
+        |row| row.get(0),
+    )
+}
+
+fn get_components(conn: &Connection, bundle_id: i32) -> Result<Vec<BundleComponent>> {
+    let mut stmt = conn.prepare(
+        "SELECT bc.component_id, p.name, p.code, p.unit, bc.quantity, p.stock
+        FROM bundle_components bc
+        JOIN products p ON p.id = bc.component_id
+        WHERE bc.bundle_id = ?
+        ORDER BY p.name"
+    )?;
+    let components = stmt.query_map([bundle_id], |row| {
+        Ok(BundleComponent {
+            component_id: row.get(0)?,
+            name: row.get(1)?,
+            code: row.get(2)?,
+            unit: row.get(3)?,
+            quantity: row.get(4)?,
+            stock: row.get(5)?,
+        })
+    })?
+    .collect::<Result<Vec<_>>>()?;
+    Ok(components)
+}
+
+pub fn get_bundle(conn: &Connection, bundle_id: i32) -> Result<Bundle> {
+    let product = product_queries::get_product(conn, bundle_id)?;
+    let components = get_components(conn, bundle_id)?;
+    if components.is_empty() {
+        return Err(validation_error(format!("El producto {} no es un paquete", bundle_id)));
+    }
+
+    // El componente más escaso limita los paquetes que pueden armarse
+    let scale = 10f64.powi(product.quantity_decimals as i32);
+    let available = components
+        .iter()
+        .map(|component| (round_quantity(component.stock / component.quantity) * scale).floor() / scale)
+        .fold(f64::INFINITY, f64::min)
+        .max(0.0);
+
+    Ok(Bundle { product, components, available })
+}
+
+// Ninguno si el producto no tiene componentes; cualquier otro error se propaga
+pub fn find_bundle(conn: &Connection, product_id: i32) -> Result<Option<Bundle>> {
+    if !is_bundle(conn, product_id)? {
+        return Ok(None);
+    }
+    get_bundle(conn, product_id).map(Some)
+}
+
+pub fn get_bundles(conn: &Connection) -> Result<Vec<Bundle>> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT bc.bundle_id
+        FROM bundle_components bc
+        JOIN products p ON p.id = bc.bundle_id
+        ORDER BY p.name"
+    )?;
+    let ids = stmt.query_map([], |row| row.get::<_, i32>(0))?
+        .collect::<Result<Vec<_>>>()?;
+    ids.into_iter().map(|id| get_bundle(conn, id)).collect()
+}
+
+// Reemplaza la lista de componentes; una lista vacía deja de tratarlo como paquete
+pub fn set_components(conn: &Connection, bundle_id: i32, components: &[NewBundleComponent]) -> Result<()> {
+    let bundle = product_queries::get_product(conn, bundle_id)?;
+    if !components.is_empty() {
+        if bundle.stock != 0.0 {
+            return Err(validation_error(format!(
+                "El producto {} tiene existencia propia; ajústela a cero antes de convertirlo en paquete",
+                bundle_id
+            )));
+        }
+        if is_component(conn, bundle_id)? {
+            return Err(validation_error(format!(
+                "El producto {} es componente de otro paquete; no puede ser a su vez un paquete",
+                bundle_id
+            )));
+        }
+    }
+
+    let mut seen = HashSet::new();
+    for component in components {
+        if component.component_id == bundle_id {
+            return Err(validation_error("Un paquete no puede contenerse a sí mismo"));
+        }
+        if !seen.insert(component.component_id) {
+            return Err(validation_error(format!(
+                "El producto {} aparece más de una vez en el paquete",
+                component.component_id
+            )));
+        }
+        if is_bundle(conn, component.component_id)? {
+            return Err(validation_error(format!(
+                "El producto {} es un paquete; no puede usarse como componente",
+                component.component_id
+            )));
+        }
+        let product = product_queries::get_product(conn, component.component_id)?;
+        product.validate_quantity(component.quantity).map_err(validation_error)?;
+    }
+
+    conn.execute("DELETE FROM bundle_components WHERE bundle_id = ?", [bundle_id])?;
+    for component in components {
+        conn.execute(
+            "INSERT INTO bundle_components (bundle_id, component_id, quantity) VALUES (?, ?, ?)",
+            params![bundle_id, component.component_id, round_quantity(component.quantity)],
+        )?;
+    }
+    Ok(())
+}
+
+// Salida de una línea de venta. Un paquete guarda la receta con la que se
+// vendió y mueve cada componente en proporción; cualquier otro producto se
+// mueve directamente.
+pub fn record_sale_line(
+    conn: &Connection,
+    user_id: Option<i32>,
+    sale_detail_id: i32,
+    movement: &NewInventoryMovement,
+) -> Result<()> {
+    let components = get_components(conn, movement.product_id)?
+        .into_iter()
+        .map(|component| (component.component_id, component.quantity))
+        .collect::<Vec<_>>();
+    for (component_id, quantity) in &components {
+        conn.execute(
+            "INSERT INTO sale_detail_components (sale_detail_id, component_id, quantity) VALUES (?, ?, ?)",
+            params![sale_detail_id, component_id, quantity],
+        )?;
+    }
+    move_line(conn, user_id, movement, &components)
+}
+
+// Reversa de una línea (anulación, cancelación o devolución) con los
+// componentes guardados al vender, no con la receta actual del paquete
+pub fn reverse_sale_line(
+    conn: &Connection,
+    user_id: Option<i32>,
+    sale_detail_id: i32,
+    movement: &NewInventoryMovement,
+) -> Result<()> {
+    let mut stmt = conn.prepare(
+        "SELECT component_id, quantity FROM sale_detail_components WHERE sale_detail_id = ?"
+    )?;
+    let components = stmt.query_map([sale_detail_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(i32, f64)>>>()?;
+    move_line(conn, user_id, movement, &components)
+}
+
+pub fn delete_sale_line(conn: &Connection, sale_detail_id: i32) -> Result<()> {
+    conn.execute("DELETE FROM sale_detail_components WHERE sale_detail_id = ?", [sale_detail_id])?;
+    Ok(())
+}
+
+fn move_line(
+    conn: &Connection,
+    user_id: Option<i32>,
+    movement: &NewInventoryMovement,
+    components: &[(i32, f64)],
+) -> Result<()> {
+    if components.is_empty() {
+        inventory_queries::record_movement(conn, user_id, movement)?;
+        return Ok(());
+    }
+
+    for (component_id, quantity) in components {
+        inventory_queries::record_movement(conn, user_id, &NewInventoryMovement {
+            product_id: *component_id,
+            movement_type: movement.movement_type,
+            quantity: movement.quantity * quantity,
+            reason: movement.reason.clone(),
+            reference_type: movement.reference_type.clone(),
+            reference_id: movement.reference_id,
+        })?;
+    }
+    Ok(())
+}
+
+// Sin llaves foráneas activas las cascadas no se disparan: un paquete se
+// borra con su receta y un componente en uso no puede borrarse
+pub fn delete_product_links(conn: &Connection, product_id: i32) -> Result<()> {
+    if is_component(conn, product_id)? {
+        return Err(validation_error(format!(
+            "El producto {} es componente de un paquete; quítelo del paquete antes de eliminarlo",
+            product_id
+        )));
+    }
+    conn.execute("DELETE FROM bundle_components WHERE bundle_id = ?", [product_id])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::connection::create_tables;
+    use super::super::super::models::inventory::MovementType;
+    use super::super::super::models::product::NewProduct;
+    use super::super::super::models::sale::NewSale;
+    use super::super::super::models::sale_detail::NewSaleDetail;
+    use super::super::super::models::user::{NewUser, UserRole};
+    use super::super::{sale_detail_queries, sale_queries, user_queries};
+
+    struct Catalog {
+        conn: Connection,
+        user_id: i32,
+    }
+
+    impl Catalog {
+        fn new() -> Self {
+            let conn = Connection::open_in_memory().unwrap();
+            create_tables(&conn).unwrap();
+            let user_id = user_queries::create_user(&conn, NewUser {
+                username: "almacen".to_string(),
+                email: "almacen@example.com".to_string(),
+                password: "Secreto123".to_string(),
+                role: UserRole::Seller,
+                full_name: "Almacén".to_string(),
+            }).unwrap();
+            Catalog { conn, user_id }
+        }
+
+        fn product(&self, code: &str, stock: f64, quantity_decimals: u32) -> i32 {
+            product_queries::create_product(&self.conn, self.user_id, NewProduct {
+                name: code.to_string(),
+                code: code.to_string(),
+                price: 10.0,
+                stock,
+                allow_backorder: false,
+                description: None,
+                plu: None,
+                unit: "pza".to_string(),
+                quantity_decimals,
+                category_id: None,
+            }).unwrap()
+        }
+
+        fn stock(&self, product_id: i32) -> f64 {
+            product_queries::get_product(&self.conn, product_id).unwrap().stock
+        }
+    }
+
+    fn component(component_id: i32, quantity: f64) -> NewBundleComponent {
+        NewBundleComponent { component_id, quantity }
+    }
+
+    #[test]
+    fn availability_is_limited_by_the_scarcest_component() {
+        let catalog = Catalog::new();
+        let wine = catalog.product("VINO", 10.0, 0);
+        let cheese = catalog.product("QUESO", 1.1, 3);
+        let basket = catalog.product("CANASTA", 0.0, 0);
+        set_components(&catalog.conn, basket, &[component(wine, 2.0), component(cheese, 0.25)]).unwrap();
+
+        // 10 / 2 = 5 canastas por el vino, 1.1 / 0.25 = 4.4 por el queso
+        assert_eq!(get_bundle(&catalog.conn, basket).unwrap().available, 4.0);
+
+        let kilo = catalog.product("KILO", 0.0, 3);
+        set_components(&catalog.conn, kilo, &[component(cheese, 0.5)]).unwrap();
+        assert_eq!(get_bundle(&catalog.conn, kilo).unwrap().available, 2.2);
+    }
+
+    #[test]
+    fn availability_never_goes_negative() {
+        let catalog = Catalog::new();
+        let wine = catalog.product("VINO", 0.0, 0);
+        let basket = catalog.product("CANASTA", 0.0, 0);
+        set_components(&catalog.conn, basket, &[component(wine, 2.0)]).unwrap();
+        catalog.conn.execute("UPDATE products SET stock = -3 WHERE id = ?", [wine]).unwrap();
+        assert_eq!(get_bundle(&catalog.conn, basket).unwrap().available, 0.0);
+    }
+
+    #[test]
+    fn components_cannot_nest_or_repeat() {
+        let catalog = Catalog::new();
+        let wine = catalog.product("VINO", 5.0, 0);
+        let basket = catalog.product("CANASTA", 0.0, 0);
+        let gift = catalog.product("REGALO", 0.0, 0);
+
+        assert!(set_components(&catalog.conn, basket, &[component(basket, 1.0)]).is_err());
+        assert!(set_components(&catalog.conn, basket, &[component(wine, 1.0), component(wine, 1.0)]).is_err());
+        assert!(set_components(&catalog.conn, wine, &[component(basket, 1.0)]).is_err());
+        set_components(&catalog.conn, basket, &[component(wine, 1.0)]).unwrap();
+        assert!(set_components(&catalog.conn, gift, &[component(basket, 1.0)]).is_err());
+        assert!(set_components(&catalog.conn, wine, &[component(gift, 1.0)]).is_err());
+        assert!(find_bundle(&catalog.conn, wine).unwrap().is_none());
+    }
+
+    #[test]
+    fn reversals_use_the_recipe_stored_with_the_line() {
+        let catalog = Catalog::new();
+        let wine = catalog.product("VINO", 10.0, 0);
+        let cheese = catalog.product("QUESO", 10.0, 0);
+        let basket = catalog.product("CANASTA", 0.0, 0);
+        set_components(&catalog.conn, basket, &[component(wine, 2.0)]).unwrap();
+
+        let sale_id = sale_queries::create_sale(&catalog.conn, NewSale {
+            customer_id: None,
+            subtotal: 0.0,
+            taxes: 0.0,
+            total: 0.0,
+            payment_method: "cash".to_string(),
+        }).unwrap();
+        let detail_id = sale_detail_queries::create_sale_detail(&catalog.conn, catalog.user_id, NewSaleDetail {
+            sale_id,
+            product_id: basket,
+            quantity: 2.0,
+            unit_price: 300.0,
+            discount: 0.0,
+            tax_percentage: 0.0,
+        }).unwrap();
+        assert_eq!(catalog.stock(wine), 6.0);
+
+        // Cambiar la receta no altera lo que regresa la devolución
+        set_components(&catalog.conn, basket, &[component(cheese, 1.0)]).unwrap();
+        reverse_sale_line(&catalog.conn, Some(catalog.user_id), detail_id, &NewInventoryMovement {
+            product_id: basket,
+            movement_type: MovementType::Return,
+            quantity: 1.0,
+            reason: None,
+            reference_type: None,
+            reference_id: None,
+        }).unwrap();
+        assert_eq!((catalog.stock(wine), catalog.stock(cheese)), (8.0, 10.0));
+    }
+}
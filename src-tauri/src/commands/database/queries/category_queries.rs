@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use chrono::NaiveDateTime;
+use super::super::errors::validation_error;
+use super::super::models::category::{Category, CategorySales, NewCategory};
+
+// Departamento, categoría y subcategoría
+const MAX_DEPTH: i32 = 3;
+
+const TREE_CTE: &str = "WITH RECURSIVE tree(id, parent_id, name, depth, path) AS (
+        SELECT id, parent_id, name, 0, name FROM categories WHERE parent_id IS NULL
+        UNION ALL
+        SELECT c.id, c.parent_id, c.name, t.depth + 1, t.path || ' > ' || c.name
+        FROM categories c JOIN tree t ON c.parent_id = t.id
+    )";
+
+pub fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS categories (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            parent_id INTEGER,
+            name TEXT NOT NULL,
+            FOREIGN KEY (parent_id) REFERENCES categories(id) ON DELETE RESTRICT
+        )",
+        [],
+    )?;
+    // Los nombres no se repiten entre hermanos; los departamentos tienen padre nulo
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_categories_sibling_name
+        ON categories(COALESCE(parent_id, 0), name COLLATE NOCASE)",
+        [],
+    )?;
+    Ok(())
+}
+
+fn row_to_category(row: &Row) -> Result<Category> {
+    Ok(Category {
+        id: row.get(0)?,
+        parent_id: row.get(1)?,
+        name: row.get(2)?,
+        depth: row.get(3)?,
+        path: row.get(4)?,
+    })
+}
+
+pub fn get_category(conn: &Connection, id: i32) -> Result<Category> {
+    conn.query_row(
+        &format!("{} SELECT id, parent_id, name, depth, path FROM tree WHERE id = ?", TREE_CTE),
+        [id],
+        row_to_category,
+    )
+}
+
+// Sin llaves foráneas activas SQLite acepta cualquier id; productos y modelos
+// se revisan aquí antes de guardar
+pub fn ensure_category(conn: &Connection, category_id: Option<i32>) -> Result<()> {
+    let Some(id) = category_id else {
+        return Ok(());
+    };
+    if get_category(conn, id).optional()?.is_none() {
+        return Err(validation_error(format!("La categoría {} no existe", id)));
+    }
+    Ok(())
+}
+
+// Árbol completo en orden de recorrido: cada categoría aparece después de su padre
+pub fn get_categories(conn: &Connection) -> Result<Vec<Category>> {
+    let mut stmt = conn.prepare(&format!(
+        "{} SELECT id, parent_id, name, depth, path FROM tree ORDER BY path COLLATE NOCASE",
+        TREE_CTE
+    ))?;
+    let categories = stmt.query_map([], row_to_category)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(categories)
+}
+
+// Niveles que cuelgan debajo de la categoría, contándola a ella
+fn subtree_height(conn: &Connection, id: i32) -> Result<i32> {
+    conn.query_row(
+        "WITH RECURSIVE subtree(id, level) AS (
+            SELECT ?, 1
+            UNION ALL
+            SELECT c.id, s.level + 1 FROM categories c JOIN subtree s ON c.parent_id = s.id
+        )
+        SELECT MAX(level) FROM subtree",
+        [id],
+        |row| row.get(0),
+    )
+}
+
+// Ubica la categoría bajo `parent_id` respetando la profundidad máxima; al
+// mover una categoría existente se revisa que no quede dentro de sí misma.
+fn validate_placement(conn: &Connection, id: Option<i32>, category: &NewCategory) -> Result<()> {
+    if category.name.trim().is_empty() {
+        return Err(validation_error("El nombre de la categoría no puede estar vacío"));
+    }
+    let Some(parent_id) = category.parent_id else {
+        return Ok(());
+    };
+
+    let parent = get_category(conn, parent_id)?;
+    let height = match id {
+        Some(id) => {
+            if parent.id == id || is_descendant(conn, parent.id, id)? {
+                return Err(validation_error("Una categoría no puede moverse dentro de sí misma"));
+            }
+            subtree_height(conn, id)?
+        }
+        None => 1,
+    };
+    if parent.depth + 1 + height > MAX_DEPTH {
+        return Err(validation_error(format!(
+            "El catálogo admite a lo más {} niveles: departamento, categoría y subcategoría",
+            MAX_DEPTH
+        )));
+    }
+    Ok(())
+}
+
+fn is_descendant(conn: &Connection, id: i32, ancestor_id: i32) -> Result<bool> {
+    conn.query_row(
+        "WITH RECURSIVE ancestors(id) AS (
+            SELECT parent_id FROM categories WHERE id = ?1
+            UNION ALL
+            SELECT c.parent_id FROM categories c JOIN ancestors a ON c.id = a.id
+        )
+        SELECT EXISTS(SELECT 1 FROM ancestors WHERE id = ?2)",
+        params![id, ancestor_id],
+        |row| row.get(0),
+    )
+}
+
+pub fn create_category(conn: &Connection, category: &NewCategory) -> Result<i32> {
+    validate_placement(conn, None, category)?;
+    conn.execute(
+        "INSERT INTO categories (parent_id, name) VALUES (?, ?)",
+        params![category.parent_id, category.name.trim()],
+    )?;
+    Ok(conn.last_insert_rowid() as i32)
+}
+
+pub fn update_category(conn: &Connection, id: i32, category: &NewCategory) -> Result<()> {
+    validate_placement(conn, Some(id), category)?;
+    conn.execute(
+        "UPDATE categories SET parent_id = ?, name = ? WHERE id = ?",
+        params![category.parent_id, category.name.trim(), id],
+    )?;
+    Ok(())
+}
+
+pub fn delete_category(conn: &Connection, id: i32) -> Result<()> {
+    let (children, products): (i64, i64) = conn.query_row(
+        "SELECT
+            (SELECT COUNT(*) FROM categories WHERE parent_id = ?1),
+            (SELECT COUNT(*) FROM products WHERE category_id = ?1)",
+        [id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    if children > 0 || products > 0 {
+        return Err(validation_error(
+            "La categoría tiene subcategorías o productos asignados; reasígnelos antes de eliminarla",
+        ));
+    }
+    conn.execute("DELETE FROM categories WHERE id = ?", [id])?;
+    Ok(())
+}
+
+// Ventas completadas del periodo acumuladas por categoría: cada renglón suma
+// lo vendido en la categoría y en todas sus subcategorías.
+pub fn get_sales_by_category(conn: &Connection, start: NaiveDateTime, end: NaiveDateTime) -> Result<Vec<CategorySales>> {
+    let start_str = start.format("%Y-%m-%d %H:%M:%S").to_string();
+    let end_str = end.format("%Y-%m-%d %H:%M:%S").to_string();
+    // Cada línea descuenta lo que se le ha devuelto, aunque la devolución caiga
+    // fuera del periodo; una venta devuelta por completo deja de contarse
+    let lines = "SELECT p.category_id, sd.sale_id,
+            sd.quantity - COALESCE(r.quantity, 0) AS quantity,
+            sd.subtotal - COALESCE(r.subtotal, 0) AS subtotal,
+            sd.tax_amount - COALESCE(r.tax_amount, 0) AS tax_amount
+        FROM sale_details sd
+        JOIN sales s ON s.id = sd.sale_id
+        JOIN products p ON p.id = sd.product_id
+        LEFT JOIN (
+            SELECT sale_detail_id, SUM(quantity) AS quantity, SUM(subtotal) AS subtotal,
+                SUM(tax_amount) AS tax_amount
+            FROM return_items GROUP BY sale_detail_id
+        ) r ON r.sale_detail_id = sd.id
+        WHERE s.status IN ('completed', 'refunded') AND s.date BETWEEN ?1 AND ?2";
+
+    let mut stmt = conn.prepare(&format!(
+        "WITH RECURSIVE descendants(ancestor_id, category_id) AS (
+            SELECT id, id FROM categories
+            UNION ALL
+            SELECT d.ancestor_id, c.id FROM categories c JOIN descendants d ON c.parent_id = d.category_id
+        ),
+        lines AS ({})
+        SELECT d.ancestor_id, COUNT(DISTINCT CASE WHEN l.quantity > 0 THEN l.sale_id END), COALESCE(SUM(l.quantity), 0),
+            COALESCE(SUM(l.subtotal), 0), COALESCE(SUM(l.tax_amount), 0)
+        FROM descendants d JOIN lines l ON l.category_id = d.category_id
+        GROUP BY d.ancestor_id",
+        lines
+    ))?;
+    let mut totals = stmt
+        .query_map(params![start_str, end_str], |row| {
+            Ok((row.get::<_, i32>(0)?, (row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)))
+        })?
+        .collect::<Result<HashMap<i32, (i64, f64, f64, f64)>>>()?;
+
+    let mut report = Vec::new();
+    for category in get_categories(conn)? {
+        let (sales_count, quantity, net_sales, taxes) = totals.remove(&category.id).unwrap_or_default();
+        report.push(CategorySales {
+            category_id: Some(category.id),
+            name: category.name,
+            path: category.path,
+            depth: category.depth,
+            sales_count,
+            quantity,
+            net_sales,
+            taxes,
+        });
+    }
+
+    let (sales_count, quantity, net_sales, taxes) = conn.query_row(
+        &format!(
+            "WITH lines AS ({})
+            SELECT COUNT(DISTINCT CASE WHEN quantity > 0 THEN sale_id END), COALESCE(SUM(quantity), 0),
+                COALESCE(SUM(subtotal), 0), COALESCE(SUM(tax_amount), 0)
+            FROM lines WHERE category_id IS NULL",
+            lines
+        ),
+        params![start_str, end_str],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    )?;
+    if sales_count > 0 {
+        report.push(CategorySales {
+            category_id: None,
+            name: "Sin categoría".to_string(),
+            path: "Sin categoría".to_string(),
+            depth: 0,
+            sales_count,
+            quantity,
+            net_sales,
+            taxes,
+        });
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::connection::create_tables;
+
+    fn connection() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        conn
+    }
+
+    fn category(parent_id: Option<i32>, name: &str) -> NewCategory {
+        NewCategory { parent_id, name: name.to_string() }
+    }
+
+    // Abarrotes > Bebidas > Refrescos, más Lácteos suelto
+    fn catalog(conn: &Connection) -> (i32, i32, i32, i32) {
+        let department = create_category(conn, &category(None, "Abarrotes")).unwrap();
+        let drinks = create_category(conn, &category(Some(department), "Bebidas")).unwrap();
+        let sodas = create_category(conn, &category(Some(drinks), "Refrescos")).unwrap();
+        let dairy = create_category(conn, &category(None, "Lácteos")).unwrap();
+        (department, drinks, sodas, dairy)
+    }
+
+    #[test]
+    fn tree_reports_depth_and_path() {
+        let conn = connection();
+        let (_, _, sodas, _) = catalog(&conn);
+        let sodas = get_category(&conn, sodas).unwrap();
+        assert_eq!((sodas.depth, sodas.path.as_str()), (2, "Abarrotes > Bebidas > Refrescos"));
+
+        let paths = get_categories(&conn).unwrap().into_iter().map(|c| c.path).collect::<Vec<_>>();
+        assert_eq!(paths, ["Abarrotes", "Abarrotes > Bebidas", "Abarrotes > Bebidas > Refrescos", "Lácteos"]);
+    }
+
+    #[test]
+    fn depth_is_limited_to_three_levels() {
+        let conn = connection();
+        let (_, drinks, sodas, dairy) = catalog(&conn);
+        assert!(create_category(&conn, &category(Some(sodas), "Cola")).is_err());
+
+        // Bebidas arrastra a Refrescos: bajo Lácteos quedaría en cuarto nivel
+        let cheeses = create_category(&conn, &category(Some(dairy), "Quesos")).unwrap();
+        assert!(update_category(&conn, drinks, &category(Some(cheeses), "Bebidas")).is_err());
+        assert!(update_category(&conn, drinks, &category(Some(dairy), "Bebidas")).is_ok());
+    }
+
+    #[test]
+    fn categories_cannot_move_inside_themselves() {
+        let conn = connection();
+        let (department, drinks, sodas, _) = catalog(&conn);
+        assert!(update_category(&conn, department, &category(Some(department), "Abarrotes")).is_err());
+        assert!(update_category(&conn, department, &category(Some(sodas), "Abarrotes")).is_err());
+        assert!(update_category(&conn, drinks, &category(Some(sodas), "Bebidas")).is_err());
+        assert!(update_category(&conn, sodas, &category(None, "Refrescos")).is_ok());
+    }
+
+    #[test]
+    fn names_are_unique_among_siblings() {
+        let conn = connection();
+        let (department, _, _, dairy) = catalog(&conn);
+        assert!(create_category(&conn, &category(Some(department), "bebidas")).is_err());
+        assert!(create_category(&conn, &category(Some(dairy), "Bebidas")).is_ok());
+        assert!(create_category(&conn, &category(None, "  ")).is_err());
+    }
+
+    #[test]
+    fn unknown_and_non_empty_categories_are_rejected() {
+        let conn = connection();
+        let (department, _, sodas, _) = catalog(&conn);
+        assert!(ensure_category(&conn, None).is_ok());
+        assert!(ensure_category(&conn, Some(sodas)).is_ok());
+        assert!(ensure_category(&conn, Some(999)).unwrap_err().to_string().contains("no existe"));
+
+        assert!(delete_category(&conn, department).is_err());
+        assert!(delete_category(&conn, sodas).is_ok());
+    }
+}
@@ -1,5 +1,8 @@
-use rusqlite::{params, Connection, Result};
-use super::super::models::customer::{Customer, NewCustomer};
+use rusqlite::{params, Connection, Result, Row};
+use super::super::connection::{add_column_if_missing, create_fts_index, fts_match_expression};
+use super::super::models::customer::{Customer, CustomerHighlights, CustomerMatch, NewCustomer};
+
+const CUSTOMER_COLUMNS: &str = "id, name, email, phone, address, rfc, store_credit";
 
 pub fn create_table(conn: &Connection) -> Result<()> {
     conn.execute(
@@ -9,13 +12,28 @@ pub fn create_table(conn: &Connection) -> Result<()> {
             email TEXT UNIQUE,
             phone TEXT,
             address TEXT,
-            rfc TEXT
+            rfc TEXT,
+            store_credit REAL NOT NULL DEFAULT 0
         )",
         [],
     )?;
+    add_column_if_missing(conn, "customers", "store_credit", "REAL NOT NULL DEFAULT 0")?;
+    create_fts_index(conn, "customers", &["name", "rfc", "email", "phone"])?;
     Ok(())
 }
 
+fn row_to_customer(row: &Row) -> Result<Customer> {
+    Ok(Customer {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        email: row.get(2)?,
+        phone: row.get(3)?,
+        address: row.get(4)?,
+        rfc: row.get(5)?,
+        store_credit: row.get(6)?,
+    })
+}
+
 pub fn create_customer(conn: &Connection, customer: NewCustomer) -> Result<i32> {
     conn.execute(
         "INSERT INTO customers (name, email, phone, address, rfc) 
@@ -28,35 +46,56 @@ pub fn create_customer(conn: &Connection, customer: NewCustomer) -> Result<i32>
 
 pub fn get_customer(conn: &Connection, id: i32) -> Result<Customer> {
     conn.query_row(
-        "SELECT id, name, email, phone, address, rfc FROM customers WHERE id = ?",
+        &format!("SELECT {} FROM customers WHERE id = ?", CUSTOMER_COLUMNS),
         [id],
-        |row| {
-            Ok(Customer {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                email: row.get(2)?,
-                phone: row.get(3)?,
-                address: row.get(4)?,
-                rfc: row.get(5)?,
-            })
-        },
+        row_to_customer,
     )
 }
 
 pub fn get_all_customers(conn: &Connection) -> Result<Vec<Customer>> {
-    let mut stmt = conn.prepare("SELECT id, name, email, phone, address, rfc FROM customers")?;
-    let customers = stmt.query_map([], |row| {
-        Ok(Customer {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            email: row.get(2)?,
-            phone: row.get(3)?,
-            address: row.get(4)?,
-            rfc: row.get(5)?,
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM customers", CUSTOMER_COLUMNS))?;
+    let customers = stmt.query_map([], row_to_customer)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(customers)
+}
+
+pub fn search_customers(conn: &Connection, text: &str, limit: u32) -> Result<Vec<CustomerMatch>> {
+    let Some(expression) = fts_match_expression(text) else {
+        return Ok(Vec::new());
+    };
+
+    let mut stmt = conn.prepare(&format!(
+        "WITH matches AS (
+            SELECT rowid AS customer_id,
+                highlight(customers_fts, 0, '<mark>', '</mark>') AS name_highlight,
+                highlight(customers_fts, 1, '<mark>', '</mark>') AS rfc_highlight,
+                highlight(customers_fts, 2, '<mark>', '</mark>') AS email_highlight,
+                highlight(customers_fts, 3, '<mark>', '</mark>') AS phone_highlight,
+                bm25(customers_fts, 10.0, 5.0, 3.0, 3.0) AS score
+            FROM customers_fts
+            WHERE customers_fts MATCH ?1
+            ORDER BY score
+            LIMIT ?2
+        )
+        SELECT {}, name_highlight, rfc_highlight, email_highlight, phone_highlight, score
+        FROM matches JOIN customers ON customers.id = matches.customer_id
+        ORDER BY score",
+        CUSTOMER_COLUMNS,
+    ))?;
+    let matches = stmt.query_map(params![expression, limit], |row| {
+        Ok(CustomerMatch {
+            customer: row_to_customer(row)?,
+            highlights: CustomerHighlights {
+                name: row.get("name_highlight")?,
+                rfc: row.get("rfc_highlight")?,
+                email: row.get("email_highlight")?,
+                phone: row.get("phone_highlight")?,
+            },
+            rank: row.get("score")?,
         })
     })?
     .collect::<Result<Vec<_>>>()?;
-    Ok(customers)
+    Ok(matches)
 }
 
 pub fn update_customer(conn: &Connection, id: i32, customer: NewCustomer) -> Result<()> {
@@ -69,6 +108,15 @@ pub fn update_customer(conn: &Connection, id: i32, customer: NewCustomer) -> Res
     Ok(())
 }
 
+// El saldo a favor se genera con devoluciones reembolsadas como crédito en tienda
+pub fn add_store_credit(conn: &Connection, id: i32, amount: f64) -> Result<()> {
+    conn.execute(
+        "UPDATE customers SET store_credit = store_credit + ? WHERE id = ?",
+        params![amount, id],
+    )?;
+    Ok(())
+}
+
 pub fn delete_customer(conn: &Connection, id: i32) -> Result<()> {
     conn.execute("DELETE FROM customers WHERE id = ?", [id])?;
     Ok(())
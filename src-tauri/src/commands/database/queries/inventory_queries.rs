@@ -0,0 +1,155 @@
+use rusqlite::{params, Connection, Result};
+use chrono::NaiveDateTime;
+use super::super::connection::rebuild_table_if_outdated;
+use super::super::errors::{insufficient_stock, validation_error};
+use super::super::models::inventory::{round_quantity, InventoryMovement, MovementType, NewInventoryMovement};
+use super::{bundle_queries, settings_queries};
+
+fn movements_table_sql(name: &str) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            product_id INTEGER NOT NULL,
+            date DATETIME DEFAULT CURRENT_TIMESTAMP,
+            movement_type TEXT NOT NULL CHECK(movement_type IN ('initial', 'sale', 'sale_void', 'return', 'purchase', 'adjustment', 'transfer', 'cancellation')),
+            quantity REAL NOT NULL CHECK(quantity != 0),
+            stock_after REAL NOT NULL,
+            reason TEXT,
+            user_id INTEGER,
+            reference_type TEXT,
+            reference_id INTEGER,
+            FOREIGN KEY (product_id) REFERENCES products(id),
+            FOREIGN KEY (user_id) REFERENCES users(id)
+        )",
+        name
+    )
+}
+
+pub fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(&movements_table_sql("inventory_movements"), [])?;
+    rebuild_table_if_outdated(conn, "inventory_movements", "quantity REAL", movements_table_sql)?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_inventory_movements_product ON inventory_movements(product_id, id)",
+        [],
+    )?;
+
+    // Los productos existentes arrancan el kárdex con su existencia actual
+    conn.execute(
+        "INSERT INTO inventory_movements (product_id, movement_type, quantity, stock_after, reason)
+        SELECT id, 'initial', stock, stock, 'Existencia al habilitar el kárdex' FROM products p
+        WHERE stock != 0
+        AND NOT EXISTS (SELECT 1 FROM inventory_movements m WHERE m.product_id = p.id)",
+        [],
+    )?;
+    Ok(())
+}
+
+// Único punto donde cambia `products.stock`: cada cambio deja su movimiento
+// con la existencia resultante.
+pub fn record_movement(
+    conn: &Connection,
+    user_id: Option<i32>,
+    movement: &NewInventoryMovement,
+) -> Result<i32> {
+    let quantity = round_quantity(movement.quantity);
+    if quantity == 0.0 {
+        return Err(validation_error("La cantidad del movimiento no puede ser cero"));
+    }
+    if bundle_queries::is_bundle(conn, movement.product_id)? {
+        return Err(validation_error(format!(
+            "El producto {} es un paquete; su existencia se calcula de sus componentes",
+            movement.product_id
+        )));
+    }
+
+    // La validación va en la misma sentencia que descuenta la existencia, de
+    // modo que dos ventas simultáneas no puedan vender la misma pieza.
+    let allow_backorders = settings_queries::get_inventory_settings(conn)?.allow_backorders;
+    let updated = conn.execute(
+        "UPDATE products SET stock = ROUND(stock + ?1, 6)
+        WHERE id = ?2 AND (?1 > 0 OR ROUND(stock + ?1, 6) >= 0 OR allow_backorder = 1 OR ?3)",
+        params![quantity, movement.product_id, allow_backorders],
+    )?;
+    if updated == 0 {
+        let available: f64 = conn.query_row(
+            "SELECT stock FROM products WHERE id = ?",
+            [movement.product_id],
+            |row| row.get(0),
+        )?;
+        return Err(insufficient_stock(movement.product_id, -quantity, available));
+    }
+    let stock_after: f64 = conn.query_row(
+        "SELECT stock FROM products WHERE id = ?",
+        [movement.product_id],
+        |row| row.get(0),
+    )?;
+
+    conn.execute(
+        "INSERT INTO inventory_movements
+        (product_id, movement_type, quantity, stock_after, reason, user_id, reference_type, reference_id)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            movement.product_id,
+            movement.movement_type.to_string(),
+            quantity,
+            stock_after,
+            movement.reason,
+            user_id,
+            movement.reference_type,
+            movement.reference_id
+        ],
+    )?;
+    Ok(conn.last_insert_rowid() as i32)
+}
+
+pub fn get_movement(conn: &Connection, id: i32) -> Result<InventoryMovement> {
+    conn.query_row(
+        "SELECT id, product_id, date, movement_type, quantity, stock_after, reason, user_id,
+        reference_type, reference_id
+        FROM inventory_movements WHERE id = ?",
+        [id],
+        row_to_movement,
+    )
+}
+
+pub fn get_product_kardex(
+    conn: &Connection,
+    product_id: i32,
+    start: Option<NaiveDateTime>,
+    end: Option<NaiveDateTime>,
+) -> Result<Vec<InventoryMovement>> {
+    let start_str = start.map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string());
+    let end_str = end.map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string());
+
+    let mut stmt = conn.prepare(
+        "SELECT id, product_id, date, movement_type, quantity, stock_after, reason, user_id,
+        reference_type, reference_id
+        FROM inventory_movements
+        WHERE product_id = ?1
+        AND (?2 IS NULL OR date >= ?2)
+        AND (?3 IS NULL OR date <= ?3)
+        ORDER BY id"
+    )?;
+    let movements = stmt
+        .query_map(params![product_id, start_str, end_str], row_to_movement)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(movements)
+}
+
+fn row_to_movement(row: &rusqlite::Row) -> Result<InventoryMovement> {
+    let date_str: String = row.get(2)?;
+    let movement_type: String = row.get(3)?;
+    Ok(InventoryMovement {
+        id: row.get(0)?,
+        product_id: row.get(1)?,
+        date: NaiveDateTime::parse_from_str(&date_str, "%Y-%m-%d %H:%M:%S")
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, Box::new(e)))?,
+        movement_type: MovementType::from(movement_type.as_str()),
+        quantity: row.get(4)?,
+        stock_after: row.get(5)?,
+        reason: row.get(6)?,
+        user_id: row.get(7)?,
+        reference_type: row.get(8)?,
+        reference_id: row.get(9)?,
+    })
+}
@@ -3,4 +3,18 @@ pub mod customer_queries;
 pub mod sale_queries;
 pub mod invoice_queries;
 pub mod user_queries;
-pub mod sale_detail_queries;
\ No newline at end of file
+pub mod sale_detail_queries;
+pub mod settings_queries;
+pub mod two_factor_queries;
+pub mod approval_queries;
+pub mod audit_queries;
+pub mod sale_chain_queries;
+pub mod inventory_queries;
+pub mod return_queries;
+pub mod payment_queries;
+pub mod register_queries;
+pub mod report_queries;
+pub mod receipt_queries;
+pub mod category_queries;
+pub mod variant_queries;
+pub mod bundle_queries;
\ No newline at end of file
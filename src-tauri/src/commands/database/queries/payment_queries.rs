@@ -0,0 +1,296 @@
+use rusqlite::{params, Connection, Result};
+use chrono::NaiveDateTime;
+use super::super::errors::validation_error;
+use super::super::models::payment::{NewSalePayment, PaymentMethod, SalePayment, TenderTotal};
+
+const AMOUNT_TOLERANCE: f64 = 0.005;
+
+pub fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS sale_payments (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            sale_id INTEGER NOT NULL,
+            method TEXT NOT NULL CHECK(method IN ('cash', 'credit', 'debit', 'transfer')),
+            amount REAL NOT NULL CHECK(amount > 0),
+            change_amount REAL NOT NULL DEFAULT 0 CHECK(change_amount >= 0),
+            reference TEXT,
+            card_last4 TEXT,
+            authorization_code TEXT,
+            FOREIGN KEY (sale_id) REFERENCES sales(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_sale_payments_sale ON sale_payments(sale_id)",
+        [],
+    )?;
+
+    // Las ventas anteriores se cobraron con una sola forma de pago
+    conn.execute(
+        "INSERT INTO sale_payments (sale_id, method, amount)
+        SELECT s.id, COALESCE(s.payment_method, 'cash'), s.total FROM sales s
+        WHERE s.status IN ('completed', 'refunded') AND s.total > 0
+        AND NOT EXISTS (SELECT 1 FROM sale_payments p WHERE p.sale_id = s.id)",
+        [],
+    )?;
+    Ok(())
+}
+
+fn validate_payment(payment: &NewSalePayment) -> Result<()> {
+    if payment.amount <= 0.0 {
+        return Err(validation_error("El importe de cada pago debe ser mayor a cero"));
+    }
+    if let Some(last4) = &payment.card_last4 {
+        if !payment.method.is_card() {
+            return Err(validation_error("Los últimos dígitos de tarjeta solo aplican a pagos con tarjeta"));
+        }
+        if last4.len() != 4 || !last4.chars().all(|c| c.is_ascii_digit()) {
+            return Err(validation_error("Capture los últimos 4 dígitos de la tarjeta"));
+        }
+    }
+    Ok(())
+}
+
+// Guarda las formas de pago de una venta y devuelve el cambio. Solo el
+// efectivo puede exceder lo que falta por cubrir.
+pub fn record_payments(conn: &Connection, sale_id: i32, total: f64, payments: &[NewSalePayment]) -> Result<f64> {
+    if payments.is_empty() {
+        return Err(validation_error("Indique al menos una forma de pago"));
+    }
+    for payment in payments {
+        validate_payment(payment)?;
+    }
+
+    let tendered: f64 = payments.iter().map(|payment| payment.amount).sum();
+    if tendered + AMOUNT_TOLERANCE < total {
+        return Err(validation_error(format!(
+            "Los pagos suman {:.2} y el total es {:.2}",
+            tendered, total
+        )));
+    }
+
+    let non_cash: f64 = payments
+        .iter()
+        .filter(|payment| payment.method != PaymentMethod::Cash)
+        .map(|payment| payment.amount)
+        .sum();
+    if non_cash > total + AMOUNT_TOLERANCE {
+        return Err(validation_error("Los pagos que no son en efectivo no pueden exceder el total"));
+    }
+
+    let change_due = ((tendered - total).max(0.0) * 100.0).round() / 100.0;
+    let mut remaining_change = change_due;
+    for payment in payments {
+        // El cambio se descuenta de los pagos en efectivo
+        let change_amount = if payment.method == PaymentMethod::Cash {
+            let change = remaining_change.min(payment.amount);
+            remaining_change -= change;
+            change
+        } else {
+            0.0
+        };
+
+        conn.execute(
+            "INSERT INTO sale_payments
+            (sale_id, method, amount, change_amount, reference, card_last4, authorization_code)
+            VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![
+                sale_id,
+                payment.method.to_string(),
+                payment.amount,
+                change_amount,
+                payment.reference,
+                payment.card_last4,
+                payment.authorization_code
+            ],
+        )?;
+    }
+
+    Ok(change_due)
+}
+
+// Forma de pago con mayor importe aplicado; es la que queda en `sales.payment_method`
+pub fn primary_method(conn: &Connection, sale_id: i32) -> Result<PaymentMethod> {
+    let method: String = conn.query_row(
+        "SELECT method FROM sale_payments WHERE sale_id = ?
+        GROUP BY method ORDER BY SUM(amount - change_amount) DESC LIMIT 1",
+        [sale_id],
+        |row| row.get(0),
+    )?;
+    Ok(PaymentMethod::from(method.as_str()))
+}
+
+pub fn get_payments_by_sale(conn: &Connection, sale_id: i32) -> Result<Vec<SalePayment>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, sale_id, method, amount, change_amount, reference, card_last4, authorization_code
+        FROM sale_payments WHERE sale_id = ? ORDER BY id"
+    )?;
+    let payments = stmt.query_map([sale_id], |row| {
+        let method: String = row.get(2)?;
+        Ok(SalePayment {
+            id: row.get(0)?,
+            sale_id: row.get(1)?,
+            method: PaymentMethod::from(method.as_str()),
+            amount: row.get(3)?,
+            change_amount: row.get(4)?,
+            reference: row.get(5)?,
+            card_last4: row.get(6)?,
+            authorization_code: row.get(7)?,
+        })
+    })?
+    .collect::<Result<Vec<_>>>()?;
+    Ok(payments)
+}
+
+// Importe neto por forma de pago: lo cobrado en ventas completadas o
+// reembolsadas menos lo devuelto en el periodo por esa misma vía. El efectivo
+// se descuenta del efectivo y la reversión a tarjeta de la tarjeta con que se
+// pagó; el crédito en tienda no devuelve dinero y no se descuenta.
+pub fn get_tender_report(conn: &Connection, start: NaiveDateTime, end: NaiveDateTime) -> Result<Vec<TenderTotal>> {
+    let start_str = start.format("%Y-%m-%d %H:%M:%S").to_string();
+    let end_str = end.format("%Y-%m-%d %H:%M:%S").to_string();
+
+    let mut stmt = conn.prepare(
+        "SELECT p.method, COUNT(*), SUM(p.amount - p.change_amount)
+        FROM sale_payments p
+        JOIN sales s ON s.id = p.sale_id
+        WHERE s.status IN ('completed', 'refunded') AND s.date BETWEEN ? AND ?
+        GROUP BY p.method
+        ORDER BY p.method"
+    )?;
+    let mut totals = stmt.query_map(params![start_str, end_str], |row| {
+        let method: String = row.get(0)?;
+        Ok(TenderTotal {
+            method: PaymentMethod::from(method.as_str()),
+            payments: row.get(1)?,
+            amount: row.get(2)?,
+        })
+    })?
+    .collect::<Result<Vec<_>>>()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT
+            CASE r.refund_method
+                WHEN 'cash' THEN 'cash'
+                ELSE COALESCE(
+                    (SELECT p.method FROM sale_payments p
+                    WHERE p.sale_id = r.sale_id AND p.method IN ('credit', 'debit')
+                    ORDER BY p.id LIMIT 1),
+                    s.payment_method
+                )
+            END AS method,
+            SUM(r.total)
+        FROM returns r
+        JOIN sales s ON s.id = r.sale_id
+        WHERE r.refund_method != 'store_credit' AND r.date BETWEEN ? AND ?
+        GROUP BY method"
+    )?;
+    let refunds = stmt.query_map(params![start_str, end_str], |row| {
+        let method: String = row.get(0)?;
+        Ok((PaymentMethod::from(method.as_str()), row.get::<_, f64>(1)?))
+    })?
+    .collect::<Result<Vec<_>>>()?;
+
+    for (method, refunded) in refunds {
+        match totals.iter_mut().find(|total| total.method == method) {
+            Some(total) => total.amount -= refunded,
+            None => totals.push(TenderTotal { method, payments: 0, amount: -refunded }),
+        }
+    }
+    totals.sort_by_key(|total| total.method.to_string());
+    Ok(totals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::connection::create_tables;
+    use super::super::super::models::sale::NewSale;
+    use super::super::sale_queries;
+
+    fn draft_sale() -> (Connection, i32) {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        let sale_id = sale_queries::create_sale(&conn, NewSale {
+            customer_id: None,
+            subtotal: 0.0,
+            taxes: 0.0,
+            total: 0.0,
+            payment_method: "cash".to_string(),
+        }).unwrap();
+        (conn, sale_id)
+    }
+
+    fn tender(method: PaymentMethod, amount: f64) -> NewSalePayment {
+        NewSalePayment { method, amount, reference: None, card_last4: None, authorization_code: None }
+    }
+
+    fn change_by_payment(conn: &Connection, sale_id: i32) -> Vec<(PaymentMethod, f64)> {
+        get_payments_by_sale(conn, sale_id).unwrap()
+            .into_iter()
+            .map(|payment| (payment.method, payment.change_amount))
+            .collect()
+    }
+
+    #[test]
+    fn change_comes_out_of_cash_tenders() {
+        let (conn, sale_id) = draft_sale();
+        let change = record_payments(&conn, sale_id, 100.0, &[
+            tender(PaymentMethod::Debit, 60.0),
+            tender(PaymentMethod::Cash, 50.0),
+        ]).unwrap();
+
+        assert_eq!(change, 10.0);
+        assert_eq!(change_by_payment(&conn, sale_id), vec![(PaymentMethod::Debit, 0.0), (PaymentMethod::Cash, 10.0)]);
+        assert_eq!(primary_method(&conn, sale_id).unwrap(), PaymentMethod::Debit);
+    }
+
+    #[test]
+    fn change_is_taken_from_cash_tenders_in_order() {
+        let (conn, sale_id) = draft_sale();
+        let change = record_payments(&conn, sale_id, 25.5, &[
+            tender(PaymentMethod::Cash, 20.0),
+            tender(PaymentMethod::Cash, 20.0),
+        ]).unwrap();
+
+        assert_eq!(change, 14.5);
+        assert_eq!(change_by_payment(&conn, sale_id), vec![(PaymentMethod::Cash, 14.5), (PaymentMethod::Cash, 0.0)]);
+    }
+
+    #[test]
+    fn payments_must_cover_the_total() {
+        let (conn, sale_id) = draft_sale();
+        let error = record_payments(&conn, sale_id, 100.0, &[tender(PaymentMethod::Cash, 99.0)])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("suman 99.00 y el total es 100.00"));
+
+        // Medio centavo de redondeo no impide cobrar
+        assert_eq!(record_payments(&conn, sale_id, 10.004, &[tender(PaymentMethod::Cash, 10.0)]).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn only_cash_may_exceed_the_total() {
+        let (conn, sale_id) = draft_sale();
+        assert!(record_payments(&conn, sale_id, 50.0, &[tender(PaymentMethod::Credit, 60.0)]).is_err());
+        assert!(record_payments(&conn, sale_id, 50.0, &[
+            tender(PaymentMethod::Transfer, 30.0),
+            tender(PaymentMethod::Debit, 30.0),
+        ]).is_err());
+        assert!(get_payments_by_sale(&conn, sale_id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn card_digits_are_validated() {
+        let (conn, sale_id) = draft_sale();
+        let mut cash = tender(PaymentMethod::Cash, 10.0);
+        cash.card_last4 = Some("1234".to_string());
+        assert!(record_payments(&conn, sale_id, 10.0, &[cash]).is_err());
+
+        let mut card = tender(PaymentMethod::Credit, 10.0);
+        card.card_last4 = Some("12a4".to_string());
+        assert!(record_payments(&conn, sale_id, 10.0, &[card]).is_err());
+
+        assert!(record_payments(&conn, sale_id, 10.0, &[tender(PaymentMethod::Cash, 0.0)]).is_err());
+    }
+}
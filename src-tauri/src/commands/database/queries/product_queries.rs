@@ -1,68 +1,651 @@
-use rusqlite::{params, Connection, Result};
-use super::super::models::product::{Product, NewProduct};
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use super::super::connection::{
+    add_column_if_missing, create_fts_index, fts_match_expression, rebuild_table_if_outdated,
+};
+use super::super::errors::validation_error;
+use super::super::models::inventory::{round_quantity, MovementType, NewInventoryMovement};
+use super::super::models::product::{
+    BarcodeType, NewProductBarcode, NewProductUnit, Product, NewProduct, ProductBarcode,
+    ProductHighlights, ProductMatch, ProductPage, ProductSearch, ProductUnit, ScannedProduct,
+};
+use super::super::models::settings::ScaleValueKind;
+use super::{bundle_queries, category_queries, inventory_queries, settings_queries, variant_queries};
 
-pub fn create_table(conn: &Connection) -> Result<()> {
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS products (
+const PRODUCT_COLUMNS: &str =
+    "id, name, code, price, stock, allow_backorder, description, plu, unit, quantity_decimals, category_id";
+const BARCODE_COLUMNS: &str = "id, product_id, barcode, barcode_type, pack_quantity";
+const MAX_PAGE_SIZE: u32 = 200;
+
+fn products_table_sql(name: &str) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             name TEXT NOT NULL,
             code TEXT UNIQUE NOT NULL,
             price REAL NOT NULL,
-            stock INTEGER NOT NULL
+            stock REAL NOT NULL,
+            allow_backorder BOOLEAN DEFAULT 0,
+            description TEXT,
+            plu TEXT,
+            unit TEXT NOT NULL DEFAULT 'pza',
+            quantity_decimals INTEGER NOT NULL DEFAULT 0 CHECK(quantity_decimals BETWEEN 0 AND 3),
+            category_id INTEGER REFERENCES categories(id)
+        )",
+        name
+    )
+}
+
+pub fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(&products_table_sql("products"), [])?;
+    add_column_if_missing(conn, "products", "allow_backorder", "BOOLEAN DEFAULT 0")?;
+    add_column_if_missing(conn, "products", "description", "TEXT")?;
+    add_column_if_missing(conn, "products", "plu", "TEXT")?;
+    // La existencia era entera; con cantidades fraccionarias pasa a REAL
+    rebuild_table_if_outdated(conn, "products", "stock REAL", products_table_sql)?;
+    add_column_if_missing(conn, "products", "category_id", "INTEGER REFERENCES categories(id)")?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_products_category ON products(category_id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_products_plu ON products(plu) WHERE plu IS NOT NULL",
+        [],
+    )?;
+    create_fts_index(conn, "products", &["name", "code", "description"])?;
+    // Solo para ordenar por nombre; el filtro de texto usa el índice FTS
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_products_name ON products(name COLLATE NOCASE)",
+        [],
+    )?;
+
+    // Códigos alternos: el mismo artículo llega con distintos EAN/UPC
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS product_barcodes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            product_id INTEGER NOT NULL,
+            barcode TEXT UNIQUE NOT NULL,
+            barcode_type TEXT NOT NULL DEFAULT 'internal'
+                CHECK(barcode_type IN ('ean13', 'upc_a', 'code128', 'internal')),
+            pack_quantity INTEGER NOT NULL DEFAULT 1 CHECK(pack_quantity > 0),
+            FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    add_column_if_missing(
+        conn,
+        "product_barcodes",
+        "barcode_type",
+        "TEXT NOT NULL DEFAULT 'internal' CHECK(barcode_type IN ('ean13', 'upc_a', 'code128', 'internal'))",
+    )?;
+    add_column_if_missing(
+        conn,
+        "product_barcodes",
+        "pack_quantity",
+        "INTEGER NOT NULL DEFAULT 1 CHECK(pack_quantity > 0)",
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_product_barcodes_product ON product_barcodes(product_id)",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS product_units (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            product_id INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            factor REAL NOT NULL CHECK(factor > 0),
+            UNIQUE(product_id, name),
+            FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE
         )",
         [],
     )?;
     Ok(())
 }
 
-pub fn create_product(conn: &Connection, product: NewProduct) -> Result<i32> {
+fn row_to_product(row: &Row) -> Result<Product> {
+    Ok(Product {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        code: row.get(2)?,
+        price: row.get(3)?,
+        stock: row.get(4)?,
+        allow_backorder: row.get(5)?,
+        description: row.get(6)?,
+        plu: row.get(7)?,
+        unit: row.get(8)?,
+        quantity_decimals: row.get(9)?,
+        category_id: row.get(10)?,
+    })
+}
+
+// Un código principal no puede repetir el código alterno de otro artículo
+fn ensure_not_alternate_barcode(conn: &Connection, code: &str) -> Result<()> {
+    let taken: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM product_barcodes WHERE barcode = ?)",
+        [code],
+        |row| row.get(0),
+    )?;
+    if taken {
+        return Err(validation_error("El código ya está registrado como código alterno de un producto"));
+    }
+    Ok(())
+}
+
+// Los PLU se guardan sin ceros a la izquierda, igual que se leen de la etiqueta
+fn normalize_plu(plu: Option<&str>) -> Result<Option<String>> {
+    let Some(plu) = plu.map(str::trim).filter(|plu| !plu.is_empty()) else {
+        return Ok(None);
+    };
+    let normalized = plu.trim_start_matches('0');
+    if !plu.chars().all(|c| c.is_ascii_digit()) || normalized.is_empty() {
+        return Err(validation_error("El PLU debe ser un número mayor a cero"));
+    }
+    Ok(Some(normalized.to_string()))
+}
+
+fn validate_unit(product: &NewProduct) -> Result<()> {
+    if product.unit.trim().is_empty() {
+        return Err(validation_error("La unidad de medida no puede estar vacía"));
+    }
+    if product.quantity_decimals > 3 {
+        return Err(validation_error("Las cantidades admiten a lo más 3 decimales"));
+    }
+    Ok(())
+}
+
+pub fn create_product(conn: &Connection, user_id: i32, product: NewProduct) -> Result<i32> {
+    ensure_not_alternate_barcode(conn, &product.code)?;
+    validate_unit(&product)?;
+    category_queries::ensure_category(conn, product.category_id)?;
+    let plu = normalize_plu(product.plu.as_deref())?;
     conn.execute(
-        "INSERT INTO products (name, code, price, stock) VALUES (?, ?, ?, ?)",
-        params![product.name, product.code, product.price, product.stock],
+        "INSERT INTO products
+        (name, code, price, stock, allow_backorder, description, plu, unit, quantity_decimals, category_id)
+        VALUES (?, ?, ?, 0, ?, ?, ?, ?, ?, ?)",
+        params![
+            product.name,
+            product.code,
+            product.price,
+            product.allow_backorder,
+            product.description,
+            plu,
+            product.unit.trim(),
+            product.quantity_decimals,
+            product.category_id
+        ],
     )?;
-    Ok(conn.last_insert_rowid() as i32)
+    let id = conn.last_insert_rowid() as i32;
+
+    if product.stock != 0.0 {
+        inventory_queries::record_movement(conn, Some(user_id), &NewInventoryMovement {
+            product_id: id,
+            movement_type: MovementType::Initial,
+            quantity: product.stock,
+            reason: None,
+            reference_type: None,
+            reference_id: None,
+        })?;
+    }
+    Ok(id)
 }
 
 pub fn get_product(conn: &Connection, id: i32) -> Result<Product> {
     conn.query_row(
-        "SELECT id, name, code, price, stock FROM products WHERE id = ?",
+        &format!("SELECT {} FROM products WHERE id = ?", PRODUCT_COLUMNS),
         [id],
-        |row| {
-            Ok(Product {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                code: row.get(2)?,
-                price: row.get(3)?,
-                stock: row.get(4)?,
-            })
-        },
+        row_to_product,
     )
 }
 
 pub fn get_all_products(conn: &Connection) -> Result<Vec<Product>> {
-    let mut stmt = conn.prepare("SELECT id, name, code, price, stock FROM products")?;
-    let products = stmt.query_map([], |row| {
-        Ok(Product {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            code: row.get(2)?,
-            price: row.get(3)?,
-            stock: row.get(4)?,
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM products", PRODUCT_COLUMNS))?;
+    let products = stmt.query_map([], row_to_product)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(products)
+}
+
+// Los lectores suelen enviar un UPC-A con un cero al frente, como EAN-13
+fn barcode_candidates(code: &str) -> Vec<String> {
+    let mut candidates = vec![code.to_string()];
+    if code.chars().all(|c| c.is_ascii_digit()) {
+        match code.len() {
+            13 if code.starts_with('0') => candidates.push(code[1..].to_string()),
+            12 => candidates.push(format!("0{}", code)),
+            _ => {}
+        }
+    }
+    candidates
+}
+
+// Etiquetas de báscula: EAN-13 válidos cuyo prefijo coincide con una regla
+fn find_by_scale_barcode(conn: &Connection, code: &str) -> Result<Option<ScannedProduct>> {
+    if BarcodeType::Ean13.validate(code).is_err() {
+        return Ok(None);
+    }
+    let Some(reading) = settings_queries::get_scale_barcode_settings(conn)?.parse(code) else {
+        return Ok(None);
+    };
+    let product = conn.query_row(
+        &format!("SELECT {} FROM products WHERE plu = ?", PRODUCT_COLUMNS),
+        [&reading.plu],
+        row_to_product,
+    ).optional()?;
+    let Some(product) = product else {
+        return Ok(None);
+    };
+
+    let (quantity, weight, embedded_price) = match reading.value_kind {
+        ScaleValueKind::Weight => (reading.value, Some(reading.value), None),
+        ScaleValueKind::Price => {
+            if product.price <= 0.0 {
+                return Err(validation_error(format!(
+                    "El producto {} no tiene precio para calcular la cantidad de la etiqueta",
+                    product.name
+                )));
+            }
+            let quantity = (reading.value / product.price * 1000.0).round() / 1000.0;
+            (quantity, None, Some(reading.value))
+        }
+    };
+    if quantity <= 0.0 {
+        return Err(validation_error("La etiqueta de báscula no tiene peso ni importe"));
+    }
+
+    Ok(Some(ScannedProduct {
+        product,
+        pack_quantity: 1,
+        quantity,
+        weight,
+        embedded_price,
+    }))
+}
+
+// Lectura del escáner: primero el código principal, luego los alternos con
+// la cantidad de piezas que representa cada uno y al final las etiquetas de
+// báscula
+pub fn find_product_by_code(conn: &Connection, code: &str) -> Result<Option<ScannedProduct>> {
+    let code = code.trim();
+    let product = conn.query_row(
+        &format!("SELECT {} FROM products WHERE code = ?", PRODUCT_COLUMNS),
+        [code],
+        row_to_product,
+    ).optional()?;
+    if let Some(product) = product {
+        return Ok(Some(ScannedProduct::pack(product, 1)));
+    }
+
+    for candidate in barcode_candidates(code) {
+        let barcode = conn.query_row(
+            &format!("SELECT {} FROM product_barcodes WHERE barcode = ?", BARCODE_COLUMNS),
+            [&candidate],
+            row_to_barcode,
+        ).optional()?;
+        if let Some(barcode) = barcode {
+            let product = get_product(conn, barcode.product_id)?;
+            return Ok(Some(ScannedProduct::pack(product, barcode.pack_quantity)));
+        }
+    }
+    find_by_scale_barcode(conn, code)
+}
+
+pub fn search_products(conn: &Connection, search: &ProductSearch) -> Result<ProductPage> {
+    let page = search.page.max(1);
+    let page_size = search.page_size.clamp(1, MAX_PAGE_SIZE);
+    // El texto se busca en el índice FTS por prefijo de palabra ("caf ver"
+    // encuentra "Café Veracruz"); un LIKE '%texto%' recorrería toda la tabla en
+    // cada tecla. Los códigos alternos se comparan completos, como al escanear.
+    let text = search.text.as_deref()
+        .map(str::trim)
+        .filter(|text| !text.is_empty());
+    let match_expression = text.and_then(fts_match_expression);
+    let filter = "(?1 IS NULL OR id IN (SELECT rowid FROM products_fts WHERE products_fts MATCH ?1)
+            OR id IN (SELECT product_id FROM product_barcodes WHERE barcode = ?3))
+        AND (?2 IS NULL OR category_id IN (
+            WITH RECURSIVE subtree(id) AS (
+                SELECT ?2
+                UNION ALL
+                SELECT c.id FROM categories c JOIN subtree s ON c.parent_id = s.id
+            )
+            SELECT id FROM subtree
+        ))";
+
+    let total: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM products WHERE {}", filter),
+        params![match_expression, search.category_id, text],
+        |row| row.get(0),
+    )?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM products WHERE {} ORDER BY {} {}, id LIMIT ?4 OFFSET ?5",
+        PRODUCT_COLUMNS,
+        filter,
+        search.sort_by.column(),
+        if search.descending { "DESC" } else { "ASC" },
+    ))?;
+    let items = stmt.query_map(
+        params![match_expression, search.category_id, text, page_size, (page - 1) * page_size],
+        row_to_product,
+    )?
+    .collect::<Result<Vec<_>>>()?;
+
+    Ok(ProductPage { items, total, page, page_size })
+}
+
+fn row_to_barcode(row: &Row) -> Result<ProductBarcode> {
+    Ok(ProductBarcode {
+        id: row.get(0)?,
+        product_id: row.get(1)?,
+        barcode: row.get(2)?,
+        barcode_type: BarcodeType::from(row.get::<_, String>(3)?.as_str()),
+        pack_quantity: row.get(4)?,
+    })
+}
+
+// Búsqueda por relevancia: el nombre pesa más que el código y este más que
+// la descripción. Las coincidencias se marcan con <mark>.
+pub fn search_products_text(conn: &Connection, text: &str, limit: u32) -> Result<Vec<ProductMatch>> {
+    let Some(expression) = fts_match_expression(text) else {
+        return Ok(Vec::new());
+    };
+
+    let mut stmt = conn.prepare(&format!(
+        "WITH matches AS (
+            SELECT rowid AS product_id,
+                highlight(products_fts, 0, '<mark>', '</mark>') AS name_highlight,
+                highlight(products_fts, 1, '<mark>', '</mark>') AS code_highlight,
+                highlight(products_fts, 2, '<mark>', '</mark>') AS description_highlight,
+                bm25(products_fts, 10.0, 5.0, 1.0) AS score
+            FROM products_fts
+            WHERE products_fts MATCH ?1
+            ORDER BY score
+            LIMIT ?2
+        )
+        SELECT {}, name_highlight, code_highlight, description_highlight, score
+        FROM matches JOIN products ON products.id = matches.product_id
+        ORDER BY score",
+        PRODUCT_COLUMNS,
+    ))?;
+    let matches = stmt.query_map(params![expression, limit], |row| {
+        Ok(ProductMatch {
+            product: row_to_product(row)?,
+            highlights: ProductHighlights {
+                name: row.get("name_highlight")?,
+                code: row.get("code_highlight")?,
+                description: row.get("description_highlight")?,
+            },
+            rank: row.get("score")?,
         })
     })?
     .collect::<Result<Vec<_>>>()?;
-    Ok(products)
+    Ok(matches)
+}
+
+pub fn add_barcode(conn: &Connection, barcode: &NewProductBarcode) -> Result<i32> {
+    let code = barcode.barcode.trim();
+    if code.is_empty() {
+        return Err(validation_error("El código de barras no puede estar vacío"));
+    }
+    barcode.barcode_type.validate(code).map_err(validation_error)?;
+    if barcode.pack_quantity <= 0 {
+        return Err(validation_error("La cantidad por empaque debe ser mayor a cero"));
+    }
+    let taken: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM products WHERE code = ?)",
+        [code],
+        |row| row.get(0),
+    )?;
+    if taken {
+        return Err(validation_error("El código ya es el código principal de un producto"));
+    }
+
+    conn.execute(
+        "INSERT INTO product_barcodes (product_id, barcode, barcode_type, pack_quantity)
+        VALUES (?, ?, ?, ?)",
+        params![barcode.product_id, code, barcode.barcode_type.to_string(), barcode.pack_quantity],
+    )?;
+    Ok(conn.last_insert_rowid() as i32)
 }
 
+pub fn get_barcodes(conn: &Connection, product_id: i32) -> Result<Vec<ProductBarcode>> {
+    let mut stmt = conn.prepare(
+        &format!("SELECT {} FROM product_barcodes WHERE product_id = ? ORDER BY id", BARCODE_COLUMNS)
+    )?;
+    let barcodes = stmt.query_map([product_id], row_to_barcode)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(barcodes)
+}
+
+pub fn get_barcode(conn: &Connection, id: i32) -> Result<ProductBarcode> {
+    conn.query_row(
+        &format!("SELECT {} FROM product_barcodes WHERE id = ?", BARCODE_COLUMNS),
+        [id],
+        row_to_barcode,
+    )
+}
+
+pub fn delete_barcode(conn: &Connection, id: i32) -> Result<()> {
+    conn.execute("DELETE FROM product_barcodes WHERE id = ?", [id])?;
+    Ok(())
+}
+
+// La existencia no se edita aquí: cualquier cambio pasa por un movimiento de
+// inventario con su motivo, así que `product.stock` se ignora
 pub fn update_product(conn: &Connection, id: i32, product: NewProduct) -> Result<()> {
+    let current = get_product(conn, id)?;
+    if product.code != current.code {
+        ensure_not_alternate_barcode(conn, &product.code)?;
+    }
+    validate_unit(&product)?;
+    category_queries::ensure_category(conn, product.category_id)?;
+    let plu = normalize_plu(product.plu.as_deref())?;
     conn.execute(
-        "UPDATE products SET name = ?, code = ?, price = ?, stock = ? WHERE id = ?",
-        params![product.name, product.code, product.price, product.stock, id],
+        "UPDATE products SET name = ?, code = ?, price = ?, allow_backorder = ?, description = ?, plu = ?,
+        unit = ?, quantity_decimals = ?, category_id = ?
+        WHERE id = ?",
+        params![
+            product.name,
+            product.code,
+            product.price,
+            product.allow_backorder,
+            product.description,
+            plu,
+            product.unit.trim(),
+            product.quantity_decimals,
+            product.category_id,
+            id
+        ],
     )?;
+    if product.price != current.price {
+        variant_queries::record_price_override(conn, id, product.price)?;
+    }
     Ok(())
 }
 
+// Un producto con kárdex ya tiene historia (ventas, entradas, ajustes) que no
+// se puede perder
 pub fn delete_product(conn: &Connection, id: i32) -> Result<()> {
+    let movements: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM inventory_movements WHERE product_id = ?",
+        [id],
+        |row| row.get(0),
+    )?;
+    if movements > 0 {
+        return Err(validation_error(format!(
+            "El producto {} tiene movimientos de inventario y no se puede eliminar",
+            id
+        )));
+    }
+    bundle_queries::delete_product_links(conn, id)?;
+    variant_queries::delete_variant_links(conn, id)?;
     conn.execute("DELETE FROM products WHERE id = ?", [id])?;
     Ok(())
-}
\ No newline at end of file
+}
+
+pub fn add_unit(conn: &Connection, unit: &NewProductUnit) -> Result<i32> {
+    let product = get_product(conn, unit.product_id)?;
+    let name = unit.name.trim();
+    if name.is_empty() {
+        return Err(validation_error("El nombre de la unidad no puede estar vacío"));
+    }
+    if name == product.unit {
+        return Err(validation_error(format!("{} ya es la unidad base del producto", name)));
+    }
+    if unit.factor <= 0.0 || !unit.factor.is_finite() {
+        return Err(validation_error("El factor de conversión debe ser mayor a cero"));
+    }
+
+    conn.execute(
+        "INSERT INTO product_units (product_id, name, factor) VALUES (?, ?, ?)",
+        params![unit.product_id, name, unit.factor],
+    )?;
+    Ok(conn.last_insert_rowid() as i32)
+}
+
+fn row_to_unit(row: &Row) -> Result<ProductUnit> {
+    Ok(ProductUnit {
+        id: row.get(0)?,
+        product_id: row.get(1)?,
+        name: row.get(2)?,
+        factor: row.get(3)?,
+    })
+}
+
+pub fn get_unit(conn: &Connection, id: i32) -> Result<ProductUnit> {
+    conn.query_row(
+        "SELECT id, product_id, name, factor FROM product_units WHERE id = ?",
+        [id],
+        row_to_unit,
+    )
+}
+
+pub fn get_units(conn: &Connection, product_id: i32) -> Result<Vec<ProductUnit>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, product_id, name, factor FROM product_units WHERE product_id = ? ORDER BY factor"
+    )?;
+    let units = stmt.query_map([product_id], row_to_unit)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(units)
+}
+
+pub fn delete_unit(conn: &Connection, id: i32) -> Result<()> {
+    conn.execute("DELETE FROM product_units WHERE id = ?", [id])?;
+    Ok(())
+}
+
+// Convierte una cantidad capturada en una unidad alterna a la unidad base
+pub fn to_base_quantity(conn: &Connection, product_id: i32, unit_id: Option<i32>, quantity: f64) -> Result<f64> {
+    let Some(unit_id) = unit_id else {
+        return Ok(quantity);
+    };
+    let unit = get_unit(conn, unit_id)?;
+    if unit.product_id != product_id {
+        return Err(validation_error(format!(
+            "La unidad {} no pertenece al producto {}",
+            unit.name, product_id
+        )));
+    }
+    Ok(round_quantity(quantity * unit.factor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::connection::create_tables;
+    use super::super::super::models::product::gtin_check_digit;
+    use super::super::super::models::user::{NewUser, UserRole};
+    use super::super::user_queries;
+
+    fn ean13(digits: &str) -> String {
+        format!("{}{}", digits, gtin_check_digit(digits).unwrap())
+    }
+
+    fn deli_product(conn: &Connection, name: &str, plu: &str, price: f64) -> i32 {
+        create_product(conn, 1, NewProduct {
+            name: name.to_string(),
+            code: format!("DELI-{}", plu),
+            price,
+            stock: 0.0,
+            allow_backorder: true,
+            description: None,
+            plu: Some(plu.to_string()),
+            unit: "kg".to_string(),
+            quantity_decimals: 3,
+            category_id: None,
+        }).unwrap()
+    }
+
+    fn connection() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn weight_labels_resolve_the_plu() {
+        let conn = connection();
+        let id = deli_product(&conn, "Jamón", "00123", 180.0);
+
+        let scanned = find_product_by_code(&conn, &ean13("200012301250")).unwrap().unwrap();
+        assert_eq!(scanned.product.id, id);
+        assert_eq!((scanned.quantity, scanned.weight, scanned.embedded_price), (1.25, Some(1.25), None));
+    }
+
+    #[test]
+    fn price_labels_derive_the_quantity_from_the_unit_price() {
+        let conn = connection();
+        deli_product(&conn, "Queso", "456", 179.99);
+
+        let scanned = find_product_by_code(&conn, &ean13("220045604500")).unwrap().unwrap();
+        assert_eq!((scanned.quantity, scanned.weight, scanned.embedded_price), (0.25, None, Some(45.0)));
+    }
+
+    #[test]
+    fn labels_with_a_bad_check_digit_or_unknown_plu_are_ignored() {
+        let conn = connection();
+        deli_product(&conn, "Jamón", "123", 180.0);
+
+        let valid = ean13("200012301250");
+        let corrupted = format!("{}{}", &valid[..12], (valid[12..].parse::<u32>().unwrap() + 1) % 10);
+        assert!(find_product_by_code(&conn, &corrupted).unwrap().is_none());
+        assert!(find_product_by_code(&conn, &ean13("200099901250")).unwrap().is_none());
+    }
+
+    #[test]
+    fn price_labels_need_a_unit_price() {
+        let conn = connection();
+        deli_product(&conn, "Muestra", "789", 0.0);
+        assert!(find_product_by_code(&conn, &ean13("220078904500")).is_err());
+    }
+
+    #[test]
+    fn editing_keeps_the_stock_and_history_blocks_deletion() {
+        let conn = connection();
+        let user_id = user_queries::create_user(&conn, NewUser {
+            username: "gerente".to_string(),
+            email: "gerente@example.com".to_string(),
+            password: "Secreto123".to_string(),
+            role: UserRole::Manager,
+            full_name: "Gerente".to_string(),
+        }).unwrap();
+        let product = |stock: f64| NewProduct {
+            name: "Taza".to_string(),
+            code: "TZ-1".to_string(),
+            price: 4.0,
+            stock,
+            allow_backorder: false,
+            description: None,
+            plu: None,
+            unit: "pza".to_string(),
+            quantity_decimals: 0,
+            category_id: None,
+        };
+        let id = create_product(&conn, user_id, product(5.0)).unwrap();
+
+        update_product(&conn, id, product(0.0)).unwrap();
+        assert_eq!(get_product(&conn, id).unwrap().stock, 5.0);
+        assert!(delete_product(&conn, id).unwrap_err().to_string().contains("movimientos de inventario"));
+
+        let empty = deli_product(&conn, "Jamón", "123", 180.0);
+        delete_product(&conn, empty).unwrap();
+    }
+}
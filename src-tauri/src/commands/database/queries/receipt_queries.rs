@@ -0,0 +1,35 @@
+use rusqlite::{params, Connection, Result};
+
+pub fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS receipt_prints (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            sale_id INTEGER NOT NULL,
+            user_id INTEGER NOT NULL,
+            is_copy BOOLEAN NOT NULL,
+            printed_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (sale_id) REFERENCES sales(id),
+            FOREIGN KEY (user_id) REFERENCES users(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// Solo la primera impresión es original; las demás salen como copia
+pub fn is_reprint(conn: &Connection, sale_id: i32) -> Result<bool> {
+    let prints: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM receipt_prints WHERE sale_id = ?",
+        [sale_id],
+        |row| row.get(0),
+    )?;
+    Ok(prints > 0)
+}
+
+pub fn record_print(conn: &Connection, sale_id: i32, user_id: i32, is_copy: bool) -> Result<i32> {
+    conn.execute(
+        "INSERT INTO receipt_prints (sale_id, user_id, is_copy) VALUES (?, ?, ?)",
+        params![sale_id, user_id, is_copy],
+    )?;
+    Ok(conn.last_insert_rowid() as i32)
+}
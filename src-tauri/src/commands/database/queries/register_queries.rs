@@ -0,0 +1,311 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use super::super::connection::add_column_if_missing;
+use super::super::errors::validation_error;
+use super::report_queries;
+use super::super::models::register::{
+    CashMovement, CashMovementType, CashRegister, CloseRegisterSession, NewCashMovement, NewCashRegister,
+    OpenRegisterSession, RegisterSession,
+};
+
+const SESSION_COLUMNS: &str = "id, register_id, user_id, opened_at, opening_float, closed_at, closed_by,
+    expected_cash, counted_cash, over_short, denominations, notes";
+
+pub fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS cash_registers (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT UNIQUE NOT NULL,
+            active BOOLEAN DEFAULT 1
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS register_sessions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            register_id INTEGER NOT NULL,
+            user_id INTEGER NOT NULL,
+            opened_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            opening_float REAL NOT NULL CHECK(opening_float >= 0),
+            closed_at DATETIME,
+            closed_by INTEGER,
+            expected_cash REAL,
+            counted_cash REAL,
+            over_short REAL,
+            denominations TEXT,
+            notes TEXT,
+            FOREIGN KEY (register_id) REFERENCES cash_registers(id),
+            FOREIGN KEY (user_id) REFERENCES users(id),
+            FOREIGN KEY (closed_by) REFERENCES users(id)
+        )",
+        [],
+    )?;
+    // Una caja solo puede tener un turno abierto y un cajero solo atiende una caja
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_register_sessions_open_register
+        ON register_sessions(register_id) WHERE closed_at IS NULL",
+        [],
+    )?;
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_register_sessions_open_user
+        ON register_sessions(user_id) WHERE closed_at IS NULL",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS cash_movements (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id INTEGER NOT NULL,
+            movement_type TEXT NOT NULL CHECK(movement_type IN ('cash_in', 'payout', 'safe_drop')),
+            amount REAL NOT NULL CHECK(amount > 0),
+            reason TEXT NOT NULL,
+            user_id INTEGER NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (session_id) REFERENCES register_sessions(id),
+            FOREIGN KEY (user_id) REFERENCES users(id)
+        )",
+        [],
+    )?;
+
+    add_column_if_missing(conn, "sales", "session_id", "INTEGER REFERENCES register_sessions(id)")?;
+    add_column_if_missing(conn, "returns", "session_id", "INTEGER REFERENCES register_sessions(id)")?;
+    Ok(())
+}
+
+pub fn create_register(conn: &Connection, register: NewCashRegister) -> Result<i32> {
+    if register.name.trim().is_empty() {
+        return Err(validation_error("El nombre de la caja es obligatorio"));
+    }
+    conn.execute(
+        "INSERT INTO cash_registers (name) VALUES (?)",
+        [register.name.trim()],
+    )?;
+    Ok(conn.last_insert_rowid() as i32)
+}
+
+pub fn get_register(conn: &Connection, id: i32) -> Result<CashRegister> {
+    conn.query_row(
+        "SELECT id, name, active FROM cash_registers WHERE id = ?",
+        [id],
+        |row| {
+            Ok(CashRegister {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                active: row.get(2)?,
+            })
+        },
+    )
+}
+
+pub fn get_all_registers(conn: &Connection) -> Result<Vec<CashRegister>> {
+    let mut stmt = conn.prepare("SELECT id, name, active FROM cash_registers ORDER BY name")?;
+    let registers = stmt.query_map([], |row| {
+        Ok(CashRegister {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            active: row.get(2)?,
+        })
+    })?
+    .collect::<Result<Vec<_>>>()?;
+    Ok(registers)
+}
+
+fn row_to_session(row: &rusqlite::Row) -> Result<RegisterSession> {
+    let denominations: Option<String> = row.get(10)?;
+    Ok(RegisterSession {
+        id: row.get(0)?,
+        register_id: row.get(1)?,
+        user_id: row.get(2)?,
+        opened_at: row.get(3)?,
+        opening_float: row.get(4)?,
+        closed_at: row.get(5)?,
+        closed_by: row.get(6)?,
+        expected_cash: row.get(7)?,
+        counted_cash: row.get(8)?,
+        over_short: row.get(9)?,
+        denominations: denominations
+            .map(|json| serde_json::from_str(&json))
+            .transpose()
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(10, rusqlite::types::Type::Text, Box::new(e)))?,
+        notes: row.get(11)?,
+    })
+}
+
+pub fn get_session(conn: &Connection, id: i32) -> Result<RegisterSession> {
+    conn.query_row(
+        &format!("SELECT {} FROM register_sessions WHERE id = ?", SESSION_COLUMNS),
+        [id],
+        row_to_session,
+    )
+}
+
+pub fn get_open_session(conn: &Connection, user_id: i32) -> Result<Option<RegisterSession>> {
+    conn.query_row(
+        &format!("SELECT {} FROM register_sessions WHERE user_id = ? AND closed_at IS NULL", SESSION_COLUMNS),
+        [user_id],
+        row_to_session,
+    )
+    .optional()
+}
+
+pub fn require_open_session(conn: &Connection, user_id: i32) -> Result<i32> {
+    get_open_session(conn, user_id)?
+        .map(|session| session.id)
+        .ok_or_else(|| validation_error("Abra un turno de caja antes de cobrar"))
+}
+
+pub fn open_session(conn: &Connection, user_id: i32, session: &OpenRegisterSession) -> Result<i32> {
+    if session.opening_float < 0.0 {
+        return Err(validation_error("El fondo inicial no puede ser negativo"));
+    }
+    if !get_register(conn, session.register_id)?.active {
+        return Err(validation_error("La caja está desactivada"));
+    }
+    if get_open_session(conn, user_id)?.is_some() {
+        return Err(validation_error("El usuario ya tiene un turno abierto"));
+    }
+    let register_busy: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM register_sessions WHERE register_id = ? AND closed_at IS NULL",
+        [session.register_id],
+        |row| row.get(0),
+    )?;
+    if register_busy > 0 {
+        return Err(validation_error("La caja ya tiene un turno abierto"));
+    }
+    report_queries::ensure_day_open(conn, session.register_id, &report_queries::today(conn)?)?;
+
+    conn.execute(
+        "INSERT INTO register_sessions (register_id, user_id, opening_float) VALUES (?, ?, ?)",
+        params![session.register_id, user_id, session.opening_float],
+    )?;
+    Ok(conn.last_insert_rowid() as i32)
+}
+
+// Efectivo que debería haber en el cajón: fondo inicial más el efectivo neto
+// cobrado en ventas del turno, menos los reembolsos en efectivo, más o menos
+// las entradas, pagos y retiros a caja fuerte.
+pub fn expected_cash(conn: &Connection, session_id: i32) -> Result<f64> {
+    let opening_float: f64 = conn.query_row(
+        "SELECT opening_float FROM register_sessions WHERE id = ?",
+        [session_id],
+        |row| row.get(0),
+    )?;
+    let cash_sales: f64 = conn.query_row(
+        "SELECT COALESCE(SUM(p.amount - p.change_amount), 0)
+        FROM sale_payments p
+        JOIN sales s ON s.id = p.sale_id
+        WHERE s.session_id = ? AND s.status IN ('completed', 'refunded') AND p.method = 'cash'",
+        [session_id],
+        |row| row.get(0),
+    )?;
+    let cash_refunds: f64 = conn.query_row(
+        "SELECT COALESCE(SUM(total), 0) FROM returns WHERE session_id = ? AND refund_method = 'cash'",
+        [session_id],
+        |row| row.get(0),
+    )?;
+    let cash_movements: f64 = conn.query_row(
+        "SELECT COALESCE(SUM(CASE WHEN movement_type = 'cash_in' THEN amount ELSE -amount END), 0)
+        FROM cash_movements WHERE session_id = ?",
+        [session_id],
+        |row| row.get(0),
+    )?;
+    Ok(opening_float + cash_sales - cash_refunds + cash_movements)
+}
+
+pub fn create_cash_movement(conn: &Connection, user_id: i32, movement: &NewCashMovement) -> Result<i32> {
+    let session_id = get_open_session(conn, user_id)?
+        .map(|session| session.id)
+        .ok_or_else(|| validation_error("Abra un turno de caja antes de registrar movimientos de efectivo"))?;
+    if movement.amount <= 0.0 {
+        return Err(validation_error("El importe debe ser mayor a cero"));
+    }
+    if movement.reason.trim().is_empty() {
+        return Err(validation_error("Indique el motivo del movimiento"));
+    }
+
+    // No puede salir más efectivo del que hay en el cajón
+    if movement.movement_type.sign() < 0.0 && movement.amount > expected_cash(conn, session_id)? + 0.005 {
+        return Err(validation_error("El importe excede el efectivo disponible en caja"));
+    }
+
+    conn.execute(
+        "INSERT INTO cash_movements (session_id, movement_type, amount, reason, user_id)
+        VALUES (?, ?, ?, ?, ?)",
+        params![
+            session_id,
+            movement.movement_type.to_string(),
+            movement.amount,
+            movement.reason.trim(),
+            user_id
+        ],
+    )?;
+    Ok(conn.last_insert_rowid() as i32)
+}
+
+fn row_to_cash_movement(row: &rusqlite::Row) -> Result<CashMovement> {
+    let movement_type: String = row.get(2)?;
+    Ok(CashMovement {
+        id: row.get(0)?,
+        session_id: row.get(1)?,
+        movement_type: CashMovementType::from(movement_type.as_str()),
+        amount: row.get(3)?,
+        reason: row.get(4)?,
+        user_id: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+pub fn get_cash_movement(conn: &Connection, id: i32) -> Result<CashMovement> {
+    conn.query_row(
+        "SELECT id, session_id, movement_type, amount, reason, user_id, created_at
+        FROM cash_movements WHERE id = ?",
+        [id],
+        row_to_cash_movement,
+    )
+}
+
+pub fn get_cash_movements(conn: &Connection, session_id: i32) -> Result<Vec<CashMovement>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, session_id, movement_type, amount, reason, user_id, created_at
+        FROM cash_movements WHERE session_id = ? ORDER BY id"
+    )?;
+    let movements = stmt
+        .query_map([session_id], row_to_cash_movement)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(movements)
+}
+
+pub fn close_session(
+    conn: &Connection,
+    user_id: i32,
+    session_id: i32,
+    close: &CloseRegisterSession,
+) -> Result<()> {
+    let session = get_session(conn, session_id)?;
+    if session.closed_at.is_some() {
+        return Err(validation_error("El turno ya está cerrado"));
+    }
+    if close.denominations.iter().any(|d| d.value <= 0.0 || d.count < 0) {
+        return Err(validation_error("El conteo por denominación no es válido"));
+    }
+
+    let round = |amount: f64| (amount * 100.0).round() / 100.0;
+    let counted = round(close.denominations.iter().map(|d| d.value * d.count as f64).sum());
+    let expected = round(expected_cash(conn, session_id)?);
+    let denominations = serde_json::to_string(&close.denominations)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+    conn.execute(
+        "UPDATE register_sessions SET closed_at = CURRENT_TIMESTAMP, closed_by = ?, expected_cash = ?,
+        counted_cash = ?, over_short = ?, denominations = ?, notes = ?
+        WHERE id = ?",
+        params![
+            user_id,
+            expected,
+            counted,
+            round(counted - expected),
+            denominations,
+            close.notes,
+            session_id
+        ],
+    )?;
+    Ok(())
+}
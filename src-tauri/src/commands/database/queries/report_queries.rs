@@ -0,0 +1,314 @@
+use rusqlite::{params, Connection, Result};
+use super::super::errors::validation_error;
+use super::super::models::payment::{PaymentMethod, TenderTotal};
+use super::super::models::report::{RegisterReport, ZReportSummary};
+use super::register_queries;
+
+pub fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS z_reports (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            register_id INTEGER NOT NULL,
+            business_date TEXT NOT NULL,
+            z_number INTEGER UNIQUE NOT NULL,
+            report TEXT NOT NULL,
+            created_by INTEGER NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (register_id, business_date),
+            FOREIGN KEY (register_id) REFERENCES cash_registers(id),
+            FOREIGN KEY (created_by) REFERENCES users(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn validate_date(business_date: &str) -> Result<()> {
+    chrono::NaiveDate::parse_from_str(business_date, "%Y-%m-%d")
+        .map(|_| ())
+        .map_err(|_| validation_error("La fecha del corte debe tener el formato AAAA-MM-DD"))
+}
+
+// Las fechas se guardan en UTC; el día de negocio es el de la hora local de
+// la tienda, para que las ventas de la noche no caigan en el corte siguiente.
+pub fn today(conn: &Connection) -> Result<String> {
+    conn.query_row("SELECT date('now', 'localtime')", [], |row| row.get(0))
+}
+
+// Día de negocio de una venta, según la hora local en que se cobró
+pub fn sale_business_date(conn: &Connection, sale_id: i32) -> Result<String> {
+    conn.query_row("SELECT date(date, 'localtime') FROM sales WHERE id = ?", [sale_id], |row| row.get(0))
+}
+
+// Una vez hecho el corte Z del día, la caja no admite más turnos, ventas ni
+// cancelaciones en esa fecha: quedarían fuera de todo corte o lo alterarían.
+pub fn ensure_day_open(conn: &Connection, register_id: i32, business_date: &str) -> Result<()> {
+    let closed: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM z_reports WHERE register_id = ? AND business_date = ?",
+        params![register_id, business_date],
+        |row| row.get(0),
+    )?;
+    if closed > 0 {
+        return Err(validation_error(format!(
+            "La caja ya tiene corte Z del día {}; no admite más operaciones en esa fecha",
+            business_date
+        )));
+    }
+    Ok(())
+}
+
+// Ventas de la caja en el día, tomadas de los turnos abiertos en ella
+pub fn build_report(conn: &Connection, register_id: i32, business_date: &str) -> Result<RegisterReport> {
+    validate_date(business_date)?;
+    register_queries::get_register(conn, register_id)?;
+
+    let (sales_count, net_sales, taxes, total): (i64, f64, f64, f64) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(s.subtotal), 0), COALESCE(SUM(s.taxes), 0), COALESCE(SUM(s.total), 0)
+        FROM sales s
+        JOIN register_sessions rs ON rs.id = s.session_id
+        WHERE rs.register_id = ? AND date(s.date, 'localtime') = ? AND s.status IN ('completed', 'refunded')",
+        params![register_id, business_date],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    )?;
+
+    let (gross_sales, discounts): (f64, f64) = conn.query_row(
+        "SELECT COALESCE(SUM(sd.unit_price * sd.quantity), 0), COALESCE(SUM(sd.discount), 0)
+        FROM sale_details sd
+        JOIN sales s ON s.id = sd.sale_id
+        JOIN register_sessions rs ON rs.id = s.session_id
+        WHERE rs.register_id = ? AND date(s.date, 'localtime') = ? AND s.status IN ('completed', 'refunded')",
+        params![register_id, business_date],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    let (canceled_count, canceled_total): (i64, f64) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(s.total), 0)
+        FROM sales s
+        JOIN register_sessions rs ON rs.id = s.session_id
+        WHERE rs.register_id = ? AND date(s.date, 'localtime') = ? AND s.status = 'canceled'",
+        params![register_id, business_date],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    let (returns_count, returns_total): (i64, f64) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(r.total), 0)
+        FROM returns r
+        JOIN register_sessions rs ON rs.id = r.session_id
+        WHERE rs.register_id = ? AND date(r.date, 'localtime') = ?",
+        params![register_id, business_date],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    let mut stmt = conn.prepare(
+        "SELECT p.method, COUNT(*), SUM(p.amount - p.change_amount)
+        FROM sale_payments p
+        JOIN sales s ON s.id = p.sale_id
+        JOIN register_sessions rs ON rs.id = s.session_id
+        WHERE rs.register_id = ? AND date(s.date, 'localtime') = ? AND s.status IN ('completed', 'refunded')
+        GROUP BY p.method
+        ORDER BY p.method"
+    )?;
+    let tenders = stmt.query_map(params![register_id, business_date], |row| {
+        let method: String = row.get(0)?;
+        Ok(TenderTotal {
+            method: PaymentMethod::from(method.as_str()),
+            payments: row.get(1)?,
+            amount: row.get(2)?,
+        })
+    })?
+    .collect::<Result<Vec<_>>>()?;
+
+    let (cash_in, payouts, safe_drops): (f64, f64, f64) = conn.query_row(
+        "SELECT
+            COALESCE(SUM(CASE WHEN cm.movement_type = 'cash_in' THEN cm.amount END), 0),
+            COALESCE(SUM(CASE WHEN cm.movement_type = 'payout' THEN cm.amount END), 0),
+            COALESCE(SUM(CASE WHEN cm.movement_type = 'safe_drop' THEN cm.amount END), 0)
+        FROM cash_movements cm
+        JOIN register_sessions rs ON rs.id = cm.session_id
+        WHERE rs.register_id = ? AND date(cm.created_at, 'localtime') = ?",
+        params![register_id, business_date],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+
+    let generated_at = conn.query_row("SELECT datetime('now')", [], |row| row.get(0))?;
+
+    Ok(RegisterReport {
+        register_id,
+        business_date: business_date.to_string(),
+        z_number: None,
+        generated_at,
+        sales_count,
+        gross_sales,
+        discounts,
+        net_sales,
+        taxes,
+        total,
+        canceled_count,
+        canceled_total,
+        returns_count,
+        returns_total,
+        tenders,
+        cash_in,
+        payouts,
+        safe_drops,
+    })
+}
+
+// Cierra el día de la caja: guarda el corte tal como se emitió, con el
+// siguiente folio Z. Un día no puede cerrarse dos veces.
+pub fn create_z_report(conn: &Connection, user_id: i32, register_id: i32, business_date: &str) -> Result<RegisterReport> {
+    let mut report = build_report(conn, register_id, business_date)?;
+    if business_date > today(conn)?.as_str() {
+        return Err(validation_error("No se puede hacer el corte Z de un día futuro"));
+    }
+
+    let already_closed: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM z_reports WHERE register_id = ? AND business_date = ?",
+        params![register_id, business_date],
+        |row| row.get(0),
+    )?;
+    if already_closed > 0 {
+        return Err(validation_error(format!(
+            "El día {} ya tiene corte Z para esta caja",
+            business_date
+        )));
+    }
+
+    let open_sessions: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM register_sessions WHERE register_id = ? AND closed_at IS NULL",
+        [register_id],
+        |row| row.get(0),
+    )?;
+    if open_sessions > 0 {
+        return Err(validation_error("Cierre el turno abierto de la caja antes del corte Z"));
+    }
+
+    let z_number: i64 = conn.query_row(
+        "SELECT COALESCE(MAX(z_number), 0) + 1 FROM z_reports",
+        [],
+        |row| row.get(0),
+    )?;
+    report.z_number = Some(z_number);
+
+    let json = serde_json::to_string(&report)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    conn.execute(
+        "INSERT INTO z_reports (register_id, business_date, z_number, report, created_by)
+        VALUES (?, ?, ?, ?, ?)",
+        params![register_id, business_date, z_number, json, user_id],
+    )?;
+    Ok(report)
+}
+
+pub fn get_z_report(conn: &Connection, z_number: i64) -> Result<RegisterReport> {
+    let json: String = conn.query_row(
+        "SELECT report FROM z_reports WHERE z_number = ?",
+        [z_number],
+        |row| row.get(0),
+    )?;
+    serde_json::from_str(&json)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+pub fn get_z_reports(conn: &Connection, register_id: Option<i32>) -> Result<Vec<ZReportSummary>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, register_id, business_date, z_number, created_by, created_at,
+        json_extract(report, '$.total')
+        FROM z_reports
+        WHERE ?1 IS NULL OR register_id = ?1
+        ORDER BY z_number DESC"
+    )?;
+    let reports = stmt.query_map([register_id], |row| {
+        Ok(ZReportSummary {
+            id: row.get(0)?,
+            register_id: row.get(1)?,
+            business_date: row.get(2)?,
+            z_number: row.get(3)?,
+            created_by: row.get(4)?,
+            created_at: row.get(5)?,
+            total: row.get(6)?,
+        })
+    })?
+    .collect::<Result<Vec<_>>>()?;
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::connection::create_tables;
+    use super::super::super::models::payment::NewSalePayment;
+    use super::super::super::models::product::NewProduct;
+    use super::super::super::models::register::{CloseRegisterSession, NewCashRegister, OpenRegisterSession};
+    use super::super::super::models::sale::NewSale;
+    use super::super::super::models::sale_detail::NewSaleDetail;
+    use super::super::super::models::user::{NewUser, UserRole};
+    use super::super::{product_queries, sale_detail_queries, sale_queries, user_queries};
+
+    #[test]
+    fn closed_days_reject_cancellations_and_future_days_cannot_close() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        let user_id = user_queries::create_user(&conn, NewUser {
+            username: "cajero".to_string(),
+            email: "cajero@example.com".to_string(),
+            password: "Secreto123".to_string(),
+            role: UserRole::Seller,
+            full_name: "Cajero".to_string(),
+        }).unwrap();
+        let register_id = register_queries::create_register(&conn, NewCashRegister { name: "Caja 1".to_string() }).unwrap();
+        let session_id = register_queries::open_session(&conn, user_id, &OpenRegisterSession { register_id, opening_float: 0.0 }).unwrap();
+        let product_id = product_queries::create_product(&conn, user_id, NewProduct {
+            name: "Taza".to_string(),
+            code: "TZ-1".to_string(),
+            price: 10.0,
+            stock: 5.0,
+            allow_backorder: false,
+            description: None,
+            plu: None,
+            unit: "pza".to_string(),
+            quantity_decimals: 0,
+            category_id: None,
+        }).unwrap();
+
+        // El borrador se abrió ayer; la venta cuenta en el día en que se cobra
+        let sale_id = sale_queries::create_sale(&conn, NewSale {
+            customer_id: None,
+            subtotal: 0.0,
+            taxes: 0.0,
+            total: 0.0,
+            payment_method: "cash".to_string(),
+        }).unwrap();
+        conn.execute("UPDATE sales SET date = datetime('now', '-1 day') WHERE id = ?", [sale_id]).unwrap();
+        sale_detail_queries::create_sale_detail(&conn, user_id, NewSaleDetail {
+            sale_id,
+            product_id,
+            quantity: 1.0,
+            unit_price: 10.0,
+            discount: 0.0,
+            tax_percentage: 0.0,
+        }).unwrap();
+        sale_queries::complete_sale(&conn, user_id, sale_id, &[NewSalePayment {
+            method: PaymentMethod::Cash,
+            amount: 10.0,
+            reference: None,
+            card_last4: None,
+            authorization_code: None,
+        }]).unwrap();
+        let business_date = today(&conn).unwrap();
+        assert_eq!(sale_business_date(&conn, sale_id).unwrap(), business_date);
+
+        register_queries::close_session(&conn, user_id, session_id, &CloseRegisterSession {
+            denominations: vec![],
+            notes: None,
+        }).unwrap();
+        let tomorrow: String = conn.query_row("SELECT date('now', 'localtime', '+1 day')", [], |row| row.get(0)).unwrap();
+        let error = create_z_report(&conn, user_id, register_id, &tomorrow).unwrap_err().to_string();
+        assert!(error.contains("día futuro"));
+
+        let report = create_z_report(&conn, user_id, register_id, &business_date).unwrap();
+        assert_eq!(report.sales_count, 1);
+        let error = sale_queries::cancel_sale(&conn, user_id, sale_id, "Error de captura").unwrap_err().to_string();
+        assert!(error.contains("ya tiene corte Z"));
+    }
+}
@@ -0,0 +1,400 @@
+use rusqlite::{params, Connection, Result};
+use chrono::NaiveDateTime;
+use super::super::connection::rebuild_table_if_outdated;
+use super::super::errors::validation_error;
+use super::super::models::inventory::{round_quantity, MovementType, NewInventoryMovement};
+use super::super::models::sale::SaleStatus;
+use super::super::models::sale_return::{NewSaleReturn, RefundMethod, ReturnItem, SaleReturn};
+use super::{bundle_queries, customer_queries, product_queries, register_queries, sale_detail_queries, sale_queries};
+
+fn return_items_table_sql(name: &str) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            return_id INTEGER NOT NULL,
+            sale_detail_id INTEGER NOT NULL,
+            product_id INTEGER NOT NULL,
+            quantity REAL NOT NULL CHECK(quantity > 0),
+            subtotal REAL NOT NULL,
+            tax_amount REAL NOT NULL,
+            restocked BOOLEAN NOT NULL,
+            FOREIGN KEY (return_id) REFERENCES returns(id) ON DELETE CASCADE,
+            FOREIGN KEY (sale_detail_id) REFERENCES sale_details(id),
+            FOREIGN KEY (product_id) REFERENCES products(id)
+        )",
+        name
+    )
+}
+
+pub fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS returns (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            sale_id INTEGER NOT NULL,
+            date DATETIME DEFAULT CURRENT_TIMESTAMP,
+            refund_method TEXT NOT NULL CHECK(refund_method IN ('cash', 'card_reversal', 'store_credit')),
+            reason TEXT NOT NULL,
+            subtotal REAL NOT NULL,
+            taxes REAL NOT NULL,
+            total REAL NOT NULL,
+            user_id INTEGER NOT NULL,
+            FOREIGN KEY (sale_id) REFERENCES sales(id),
+            FOREIGN KEY (user_id) REFERENCES users(id)
+        )",
+        [],
+    )?;
+    conn.execute(&return_items_table_sql("return_items"), [])?;
+    rebuild_table_if_outdated(conn, "return_items", "quantity REAL", return_items_table_sql)?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_return_items_detail ON return_items(sale_detail_id)",
+        [],
+    )?;
+    Ok(())
+}
+
+fn round_amount(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+// Cantidad e importes ya devueltos de una línea de venta
+fn returned_so_far(conn: &Connection, sale_detail_id: i32) -> Result<(f64, f64, f64)> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(quantity), 0), COALESCE(SUM(subtotal), 0), COALESCE(SUM(tax_amount), 0)
+        FROM return_items WHERE sale_detail_id = ?",
+        [sale_detail_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )
+}
+
+// Registra la devolución de una venta completada. El importe de cada línea es
+// la parte proporcional del subtotal (ya con descuento) y del impuesto de la
+// línea original; al devolver lo último de una línea se toma el remanente
+// para que la suma de las devoluciones cuadre al centavo.
+pub fn create_return(conn: &Connection, user_id: i32, sale_return: &NewSaleReturn) -> Result<i32> {
+    if sale_return.items.is_empty() {
+        return Err(validation_error("Seleccione al menos un producto a devolver"));
+    }
+    if sale_return.reason.trim().is_empty() {
+        return Err(validation_error("Indique el motivo de la devolución"));
+    }
+
+    let sale = sale_queries::get_sale(conn, sale_return.sale_id)?;
+    if SaleStatus::from(sale.status.as_str()) != SaleStatus::Completed {
+        return Err(validation_error("Solo se pueden hacer devoluciones de ventas completadas"));
+    }
+    if sale_return.refund_method == RefundMethod::StoreCredit && sale.customer_id.is_none() {
+        return Err(validation_error("El crédito en tienda requiere una venta con cliente"));
+    }
+
+    // Como el cobro, toda devolución se registra en el turno abierto; el
+    // reembolso en efectivo además sale de su cajón
+    let session_id = register_queries::require_open_session(conn, user_id)?;
+
+    conn.execute(
+        "INSERT INTO returns (sale_id, refund_method, reason, subtotal, taxes, total, user_id, session_id)
+        VALUES (?, ?, ?, 0, 0, 0, ?, ?)",
+        params![
+            sale.id,
+            sale_return.refund_method.to_string(),
+            sale_return.reason.trim(),
+            user_id,
+            session_id
+        ],
+    )?;
+    let return_id = conn.last_insert_rowid() as i32;
+
+    let mut subtotal = 0.0;
+    let mut taxes = 0.0;
+    for item in &sale_return.items {
+        let detail = sale_detail_queries::get_sale_detail(conn, item.sale_detail_id)?;
+        if detail.sale_id != sale.id {
+            return Err(validation_error(format!(
+                "La línea {} no pertenece a la venta {}",
+                item.sale_detail_id, sale.id
+            )));
+        }
+
+        product_queries::get_product(conn, detail.product_id)?
+            .validate_quantity(item.quantity)
+            .map_err(validation_error)?;
+
+        let (returned, returned_subtotal, returned_tax) = returned_so_far(conn, detail.id)?;
+        let available = round_quantity(detail.quantity - returned);
+        let quantity = round_quantity(item.quantity);
+        if quantity <= 0.0 || quantity > available {
+            return Err(validation_error(format!(
+                "Solo se pueden devolver {} unidades de la línea {}",
+                available, detail.id
+            )));
+        }
+
+        let (item_subtotal, item_tax) = if quantity == available {
+            (
+                round_amount(detail.subtotal - returned_subtotal),
+                round_amount(detail.tax_amount - returned_tax),
+            )
+        } else {
+            let ratio = quantity / detail.quantity;
+            (round_amount(detail.subtotal * ratio), round_amount(detail.tax_amount * ratio))
+        };
+        conn.execute(
+            "INSERT INTO return_items
+            (return_id, sale_detail_id, product_id, quantity, subtotal, tax_amount, restocked)
+            VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![
+                return_id,
+                detail.id,
+                detail.product_id,
+                quantity,
+                item_subtotal,
+                item_tax,
+                item.restock
+            ],
+        )?;
+
+        if item.restock {
+            bundle_queries::reverse_sale_line(conn, Some(user_id), detail.id, &NewInventoryMovement {
+                product_id: detail.product_id,
+                movement_type: MovementType::Return,
+                quantity,
+                reason: Some(sale_return.reason.trim().to_string()),
+                reference_type: Some("return".to_string()),
+                reference_id: Some(return_id),
+            })?;
+        }
+
+        subtotal += item_subtotal;
+        taxes += item_tax;
+    }
+
+    let total = round_amount(subtotal + taxes);
+    conn.execute(
+        "UPDATE returns SET subtotal = ?, taxes = ?, total = ? WHERE id = ?",
+        params![round_amount(subtotal), round_amount(taxes), total, return_id],
+    )?;
+
+    if let (RefundMethod::StoreCredit, Some(customer_id)) = (sale_return.refund_method, sale.customer_id) {
+        customer_queries::add_store_credit(conn, customer_id, total)?;
+    }
+
+    // Si ya no queda nada por devolver, la venta pasa a reembolsada
+    let pending: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sale_details sd
+        WHERE sd.sale_id = ?
+        AND sd.quantity > ROUND((SELECT COALESCE(SUM(ri.quantity), 0) FROM return_items ri WHERE ri.sale_detail_id = sd.id), 6)",
+        [sale.id],
+        |row| row.get(0),
+    )?;
+    if pending == 0 {
+        sale_queries::mark_refunded(conn, sale.id)?;
+    }
+
+    Ok(return_id)
+}
+
+fn get_return_items(conn: &Connection, return_id: i32) -> Result<Vec<ReturnItem>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, return_id, sale_detail_id, product_id, quantity, subtotal, tax_amount, restocked
+        FROM return_items WHERE return_id = ? ORDER BY id"
+    )?;
+    let items = stmt.query_map([return_id], |row| {
+        Ok(ReturnItem {
+            id: row.get(0)?,
+            return_id: row.get(1)?,
+            sale_detail_id: row.get(2)?,
+            product_id: row.get(3)?,
+            quantity: row.get(4)?,
+            subtotal: row.get(5)?,
+            tax_amount: row.get(6)?,
+            restocked: row.get(7)?,
+        })
+    })?
+    .collect::<Result<Vec<_>>>()?;
+    Ok(items)
+}
+
+fn row_to_return(row: &rusqlite::Row) -> Result<SaleReturn> {
+    let date_str: String = row.get(2)?;
+    let refund_method: String = row.get(3)?;
+    Ok(SaleReturn {
+        id: row.get(0)?,
+        sale_id: row.get(1)?,
+        date: NaiveDateTime::parse_from_str(&date_str, "%Y-%m-%d %H:%M:%S")
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, Box::new(e)))?,
+        refund_method: RefundMethod::from(refund_method.as_str()),
+        reason: row.get(4)?,
+        subtotal: row.get(5)?,
+        taxes: row.get(6)?,
+        total: row.get(7)?,
+        user_id: row.get(8)?,
+        items: Vec::new(),
+    })
+}
+
+pub fn get_return(conn: &Connection, id: i32) -> Result<SaleReturn> {
+    let mut sale_return = conn.query_row(
+        "SELECT id, sale_id, date, refund_method, reason, subtotal, taxes, total, user_id
+        FROM returns WHERE id = ?",
+        [id],
+        row_to_return,
+    )?;
+    sale_return.items = get_return_items(conn, id)?;
+    Ok(sale_return)
+}
+
+pub fn get_returns_by_sale(conn: &Connection, sale_id: i32) -> Result<Vec<SaleReturn>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, sale_id, date, refund_method, reason, subtotal, taxes, total, user_id
+        FROM returns WHERE sale_id = ? ORDER BY id"
+    )?;
+    let mut returns = stmt
+        .query_map([sale_id], row_to_return)?
+        .collect::<Result<Vec<_>>>()?;
+    for sale_return in &mut returns {
+        sale_return.items = get_return_items(conn, sale_return.id)?;
+    }
+    Ok(returns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::connection::create_tables;
+    use super::super::super::models::payment::{NewSalePayment, PaymentMethod};
+    use super::super::super::models::product::NewProduct;
+    use super::super::super::models::register::{NewCashRegister, OpenRegisterSession};
+    use super::super::super::models::sale::NewSale;
+    use super::super::super::models::sale_detail::NewSaleDetail;
+    use super::super::super::models::sale_return::NewReturnItem;
+    use super::super::super::models::user::{NewUser, UserRole};
+    use super::super::{payment_queries, user_queries};
+
+    // Venta cobrada de 3 piezas a 4.00 con 2.00 de descuento: subtotal 10.00
+    // e impuesto 1.60, que no se reparten exacto entre las tres piezas
+    fn completed_sale() -> (Connection, i32, i32, i32) {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        let user_id = user_queries::create_user(&conn, NewUser {
+            username: "cajero".to_string(),
+            email: "cajero@example.com".to_string(),
+            password: "Secreto123".to_string(),
+            role: UserRole::Seller,
+            full_name: "Cajero".to_string(),
+        }).unwrap();
+        let register_id = register_queries::create_register(&conn, NewCashRegister { name: "Caja 1".to_string() }).unwrap();
+        register_queries::open_session(&conn, user_id, &OpenRegisterSession { register_id, opening_float: 500.0 }).unwrap();
+        let product_id = product_queries::create_product(&conn, user_id, NewProduct {
+            name: "Taza".to_string(),
+            code: "TZ-1".to_string(),
+            price: 4.0,
+            stock: 10.0,
+            allow_backorder: false,
+            description: None,
+            plu: None,
+            unit: "pza".to_string(),
+            quantity_decimals: 0,
+            category_id: None,
+        }).unwrap();
+
+        let sale_id = sale_queries::create_sale(&conn, NewSale {
+            customer_id: None,
+            subtotal: 0.0,
+            taxes: 0.0,
+            total: 0.0,
+            payment_method: "cash".to_string(),
+        }).unwrap();
+        let detail_id = sale_detail_queries::create_sale_detail(&conn, user_id, NewSaleDetail {
+            sale_id,
+            product_id,
+            quantity: 3.0,
+            unit_price: 4.0,
+            discount: 2.0,
+            tax_percentage: 16.0,
+        }).unwrap();
+        sale_queries::complete_sale(&conn, user_id, sale_id, &[NewSalePayment {
+            method: PaymentMethod::Cash,
+            amount: 20.0,
+            reference: None,
+            card_last4: None,
+            authorization_code: None,
+        }]).unwrap();
+        (conn, user_id, sale_id, detail_id)
+    }
+
+    fn return_units(conn: &Connection, user_id: i32, sale_id: i32, detail_id: i32, quantity: f64) -> Result<i32> {
+        create_return(conn, user_id, &NewSaleReturn {
+            sale_id,
+            refund_method: RefundMethod::Cash,
+            reason: "Defecto".to_string(),
+            items: vec![NewReturnItem { sale_detail_id: detail_id, quantity, restock: true }],
+        })
+    }
+
+    #[test]
+    fn partial_returns_take_a_proportional_share() {
+        let (conn, user_id, sale_id, detail_id) = completed_sale();
+        let return_id = return_units(&conn, user_id, sale_id, detail_id, 1.0).unwrap();
+
+        let sale_return = get_return(&conn, return_id).unwrap();
+        assert_eq!((sale_return.subtotal, sale_return.taxes, sale_return.total), (3.33, 0.53, 3.86));
+        assert_eq!(product_queries::get_product(&conn, sale_return.items[0].product_id).unwrap().stock, 8.0);
+        assert_eq!(sale_queries::get_sale(&conn, sale_id).unwrap().status, "completed");
+    }
+
+    #[test]
+    fn tender_report_is_net_of_refunds() {
+        let (conn, user_id, sale_id, detail_id) = completed_sale();
+        return_units(&conn, user_id, sale_id, detail_id, 1.0).unwrap();
+
+        let start = NaiveDateTime::parse_from_str("2000-01-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let end = NaiveDateTime::parse_from_str("2100-01-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let tenders = payment_queries::get_tender_report(&conn, start, end).unwrap();
+        assert_eq!(tenders.len(), 1);
+        assert_eq!((tenders[0].method, tenders[0].payments), (PaymentMethod::Cash, 1));
+        assert_eq!(round_amount(tenders[0].amount), 7.74);
+    }
+
+    #[test]
+    fn last_unit_takes_the_remainder() {
+        let (conn, user_id, sale_id, detail_id) = completed_sale();
+        return_units(&conn, user_id, sale_id, detail_id, 1.0).unwrap();
+        return_units(&conn, user_id, sale_id, detail_id, 1.0).unwrap();
+        let last = get_return(&conn, return_units(&conn, user_id, sale_id, detail_id, 1.0).unwrap()).unwrap();
+        assert_eq!((last.subtotal, last.taxes), (3.34, 0.54));
+
+        // Las tres devoluciones suman exactamente la línea original
+        let (quantity, subtotal, taxes) = returned_so_far(&conn, detail_id).unwrap();
+        assert_eq!((quantity, round_amount(subtotal), round_amount(taxes)), (3.0, 10.0, 1.6));
+        assert_eq!(sale_queries::get_sale(&conn, sale_id).unwrap().status, "refunded");
+    }
+
+    #[test]
+    fn returns_cannot_exceed_what_is_left() {
+        let (conn, user_id, sale_id, detail_id) = completed_sale();
+        return_units(&conn, user_id, sale_id, detail_id, 2.0).unwrap();
+        let error = return_units(&conn, user_id, sale_id, detail_id, 2.0).unwrap_err().to_string();
+        assert!(error.contains("Solo se pueden devolver 1 unidades"));
+        assert!(return_units(&conn, user_id, sale_id, detail_id, 0.0).is_err());
+        let error = return_units(&conn, user_id, sale_id, detail_id, 0.5).unwrap_err().to_string();
+        assert!(error.contains("admite a lo más 0 decimales"));
+    }
+
+    #[test]
+    fn every_refund_needs_an_open_session() {
+        let (conn, _, sale_id, detail_id) = completed_sale();
+        let other_id = user_queries::create_user(&conn, NewUser {
+            username: "cajero2".to_string(),
+            email: "cajero2@example.com".to_string(),
+            password: "Secreto123".to_string(),
+            role: UserRole::Seller,
+            full_name: "Cajero 2".to_string(),
+        }).unwrap();
+        let refund = create_return(&conn, other_id, &NewSaleReturn {
+            sale_id,
+            refund_method: RefundMethod::CardReversal,
+            reason: "Defecto".to_string(),
+            items: vec![NewReturnItem { sale_detail_id: detail_id, quantity: 1.0, restock: true }],
+        });
+        assert!(refund.is_err());
+    }
+}
@@ -0,0 +1,413 @@
+use std::collections::HashMap;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use sha2::{Digest, Sha256};
+use super::super::errors::validation_error;
+use super::super::models::sale::Sale;
+use super::super::models::sale_chain::{ChainVerification, SaleSeal};
+use super::{sale_detail_queries, sale_queries};
+
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// Qué cubre el sello: el encabezado de la venta (folio, fecha, cliente,
+// importes y forma de pago principal), cada una de sus líneas y el hash de la
+// venta sellada anterior. Pagos, devoluciones y facturas viven en sus propias
+// tablas y no forman parte del sello.
+//
+// Además, `sale_chain_log` es una bitácora encadenada de solo inserción con
+// cada sellado y cada cambio de estado posterior (cancelación, devolución
+// total). Guarda el último eslabón fuera de `sales`, así que borrar las
+// últimas ventas o cambiar su estado por SQL también se detecta.
+pub fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS sale_chain_log (
+            seq INTEGER PRIMARY KEY,
+            sale_id INTEGER NOT NULL,
+            status TEXT NOT NULL,
+            sale_hash TEXT NOT NULL,
+            previous_hash TEXT NOT NULL,
+            hash TEXT NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS sale_chain_log_no_update
+        BEFORE UPDATE ON sale_chain_log
+        BEGIN
+            SELECT RAISE(ABORT, 'La bitácora de la cadena no se puede modificar');
+        END",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS sale_chain_log_no_delete
+        BEFORE DELETE ON sale_chain_log
+        BEGIN
+            SELECT RAISE(ABORT, 'La bitácora de la cadena no se puede modificar');
+        END",
+        [],
+    )?;
+
+    // Bases anteriores a la bitácora: se registra la cadena tal como está
+    let logged: i64 = conn.query_row("SELECT COUNT(*) FROM sale_chain_log", [], |row| row.get(0))?;
+    if logged == 0 {
+        let mut stmt = conn.prepare(
+            "SELECT id, status FROM sales WHERE chain_seq IS NOT NULL ORDER BY chain_seq"
+        )?;
+        let sealed = stmt.query_map([], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<Result<Vec<_>>>()?;
+        for (sale_id, status) in sealed {
+            append_log(conn, sale_id, "completed")?;
+            if status != "completed" {
+                append_log(conn, sale_id, &status)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn sha256_hex(payload: &str) -> String {
+    Sha256::digest(payload.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn log_hash(seq: i64, sale_id: i32, status: &str, sale_hash: &str, previous_hash: &str) -> String {
+    sha256_hex(&format!("log:{}|{}|{}|{}|prev:{}", seq, sale_id, status, sale_hash, previous_hash))
+}
+
+fn append_log(conn: &Connection, sale_id: i32, status: &str) -> Result<()> {
+    let sale_hash: String = conn.query_row(
+        "SELECT hash FROM sales WHERE id = ? AND hash IS NOT NULL",
+        [sale_id],
+        |row| row.get(0),
+    )?;
+    let last: Option<(i64, String)> = conn
+        .query_row(
+            "SELECT seq, hash FROM sale_chain_log ORDER BY seq DESC LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let (seq, previous_hash) = match last {
+        Some((seq, hash)) => (seq + 1, hash),
+        None => (1, GENESIS_HASH.to_string()),
+    };
+
+    conn.execute(
+        "INSERT INTO sale_chain_log (seq, sale_id, status, sale_hash, previous_hash, hash)
+        VALUES (?, ?, ?, ?, ?, ?)",
+        params![
+            seq,
+            sale_id,
+            status,
+            sale_hash,
+            previous_hash,
+            log_hash(seq, sale_id, status, &sale_hash, &previous_hash)
+        ],
+    )?;
+    Ok(())
+}
+
+// Registra en la bitácora el cambio de estado de una venta ya sellada
+pub fn record_status(conn: &Connection, sale_id: i32, status: &str) -> Result<()> {
+    let sealed: bool = conn.query_row(
+        "SELECT hash IS NOT NULL FROM sales WHERE id = ?",
+        [sale_id],
+        |row| row.get(0),
+    )?;
+    if sealed {
+        append_log(conn, sale_id, status)?;
+    }
+    Ok(())
+}
+
+// Representación canónica de la venta que se firma. El estado no forma parte
+// del sello: cancelar o devolver una venta es un evento legítimo posterior.
+fn canonical_payload(conn: &Connection, sale: &Sale, previous_hash: &str) -> Result<String> {
+    let mut payload = format!(
+        "v1|sale:{}|{}|{}|{:.2}|{:.2}|{:.2}|{}\n",
+        sale.id,
+        sale.date.format("%Y-%m-%d %H:%M:%S"),
+        sale.customer_id.map(|id| id.to_string()).unwrap_or_default(),
+        sale.subtotal,
+        sale.taxes,
+        sale.total,
+        sale.payment_method
+    );
+
+    let mut details = sale_detail_queries::get_details_by_sale(conn, sale.id)?;
+    details.sort_by_key(|detail| detail.id);
+    for detail in details {
+        payload.push_str(&format!(
+            "line:{}|{}|{}|{:.2}|{:.2}|{:.2}|{:.2}\n",
+            detail.id,
+            detail.product_id,
+            detail.quantity,
+            detail.unit_price,
+            detail.discount,
+            detail.subtotal,
+            detail.tax_amount
+        ));
+    }
+
+    payload.push_str(&format!("prev:{}", previous_hash));
+    Ok(payload)
+}
+
+fn compute_hash(conn: &Connection, sale: &Sale, previous_hash: &str) -> Result<String> {
+    Ok(sha256_hex(&canonical_payload(conn, sale, previous_hash)?))
+}
+
+pub fn seal_sale(conn: &Connection, sale_id: i32) -> Result<SaleSeal> {
+    let sale = sale_queries::get_sale(conn, sale_id)?;
+    if sale.status != "completed" {
+        return Err(validation_error("Solo se pueden sellar ventas completadas"));
+    }
+
+    let already_sealed: Option<String> = conn.query_row(
+        "SELECT hash FROM sales WHERE id = ?",
+        [sale_id],
+        |row| row.get(0),
+    )?;
+    if already_sealed.is_some() {
+        return Err(validation_error(format!("La venta {} ya está sellada", sale_id)));
+    }
+
+    let last: Option<(i64, String)> = conn
+        .query_row(
+            "SELECT chain_seq, hash FROM sales WHERE chain_seq IS NOT NULL
+            ORDER BY chain_seq DESC LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let (chain_seq, previous_hash) = match last {
+        Some((seq, hash)) => (seq + 1, hash),
+        None => (1, GENESIS_HASH.to_string()),
+    };
+
+    let hash = compute_hash(conn, &sale, &previous_hash)?;
+    conn.execute(
+        "UPDATE sales SET chain_seq = ?, previous_hash = ?, hash = ?, sealed_at = CURRENT_TIMESTAMP
+        WHERE id = ?",
+        params![chain_seq, previous_hash, hash, sale_id],
+    )?;
+    append_log(conn, sale_id, "completed")?;
+
+    Ok(SaleSeal {
+        sale_id,
+        chain_seq,
+        previous_hash,
+        hash,
+    })
+}
+
+// Recorre la cadena en orden y recalcula cada sello a partir de los datos
+// actuales; después contrasta ventas y estados con la bitácora. Se detiene en
+// el primer eslabón que no coincide.
+pub fn verify_chain(conn: &Connection) -> Result<ChainVerification> {
+    let mut stmt = conn.prepare(
+        "SELECT id, chain_seq, previous_hash, hash, status FROM sales
+        WHERE chain_seq IS NOT NULL ORDER BY chain_seq"
+    )?;
+    let links = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    // Ventas cerradas antes de existir la cadena; no se pueden verificar
+    let unsealed_sales: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sales
+        WHERE hash IS NULL AND status != 'draft'
+            AND id < COALESCE((SELECT MIN(id) FROM sales WHERE hash IS NOT NULL), id + 1)",
+        [],
+        |row| row.get(0),
+    )?;
+
+    let mut checked = 0;
+    let broken = |sale_id: i32, checked: i64, reason: &str| ChainVerification {
+        valid: false,
+        sealed_sales: checked,
+        unsealed_sales,
+        first_broken_sale_id: Some(sale_id),
+        reason: Some(reason.to_string()),
+    };
+
+    let mut expected_previous = GENESIS_HASH.to_string();
+    let mut sealed = HashMap::new();
+    let mut chain_order = Vec::new();
+    for (sale_id, chain_seq, previous_hash, hash, status) in links {
+        if chain_seq != checked + 1 {
+            return Ok(broken(sale_id, checked, "Falta una venta sellada antes de esta en la cadena"));
+        }
+        if previous_hash != expected_previous {
+            return Ok(broken(sale_id, checked, "El enlace con la venta anterior no coincide"));
+        }
+        let sale = sale_queries::get_sale(conn, sale_id)?;
+        if compute_hash(conn, &sale, &previous_hash)? != hash {
+            return Ok(broken(sale_id, checked, "Los datos de la venta o de sus líneas fueron modificados"));
+        }
+
+        expected_previous = hash.clone();
+        sealed.insert(sale_id, (hash, status));
+        chain_order.push(sale_id);
+        checked += 1;
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT seq, sale_id, status, sale_hash, previous_hash, hash FROM sale_chain_log ORDER BY seq"
+    )?;
+    let entries = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i32>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut expected_previous = GENESIS_HASH.to_string();
+    let mut logged_status: HashMap<i32, String> = HashMap::new();
+    for (index, (seq, sale_id, status, sale_hash, previous_hash, hash)) in entries.into_iter().enumerate() {
+        if seq != index as i64 + 1
+            || previous_hash != expected_previous
+            || log_hash(seq, sale_id, &status, &sale_hash, &previous_hash) != hash
+        {
+            return Ok(broken(sale_id, checked, "La bitácora de la cadena fue alterada"));
+        }
+        match sealed.get(&sale_id) {
+            Some((seal, _)) if *seal == sale_hash => {}
+            _ => return Ok(broken(sale_id, checked, "Una venta sellada fue eliminada o se reemplazó su sello")),
+        }
+        logged_status.insert(sale_id, status);
+        expected_previous = hash;
+    }
+
+    for sale_id in chain_order {
+        let (_, status) = &sealed[&sale_id];
+        match logged_status.get(&sale_id) {
+            Some(logged) if logged == status => {}
+            Some(_) => return Ok(broken(sale_id, checked, "El estado de la venta no coincide con el registrado")),
+            None => return Ok(broken(sale_id, checked, "La venta sellada no aparece en la bitácora de la cadena")),
+        }
+    }
+
+    // Solo las ventas cobradas llevan sesión de caja; un borrador cancelado
+    // nunca se completó y no tiene por qué estar sellado
+    let unsealed: Option<i32> = conn
+        .query_row(
+            "SELECT id FROM sales
+            WHERE hash IS NULL AND status != 'draft' AND session_id IS NOT NULL
+                AND id > (SELECT MIN(id) FROM sales WHERE hash IS NOT NULL)
+            ORDER BY id LIMIT 1",
+            [],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(sale_id) = unsealed {
+        return Ok(broken(sale_id, checked, "La venta se cerró sin sellarse en la cadena"));
+    }
+
+    Ok(ChainVerification {
+        valid: true,
+        sealed_sales: checked,
+        unsealed_sales,
+        first_broken_sale_id: None,
+        reason: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::connection::create_tables;
+    use super::super::super::models::payment::{NewSalePayment, PaymentMethod};
+    use super::super::super::models::product::NewProduct;
+    use super::super::super::models::register::{NewCashRegister, OpenRegisterSession};
+    use super::super::super::models::sale::NewSale;
+    use super::super::super::models::sale_detail::NewSaleDetail;
+    use super::super::super::models::user::{NewUser, UserRole};
+    use super::super::{product_queries, register_queries, user_queries};
+
+    fn draft(conn: &Connection) -> i32 {
+        sale_queries::create_sale(conn, NewSale {
+            customer_id: None,
+            subtotal: 0.0,
+            taxes: 0.0,
+            total: 0.0,
+            payment_method: "cash".to_string(),
+        }).unwrap()
+    }
+
+    #[test]
+    fn canceled_drafts_do_not_break_the_chain() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        let user_id = user_queries::create_user(&conn, NewUser {
+            username: "cajero".to_string(),
+            email: "cajero@example.com".to_string(),
+            password: "Secreto123".to_string(),
+            role: UserRole::Seller,
+            full_name: "Cajero".to_string(),
+        }).unwrap();
+        let register_id = register_queries::create_register(&conn, NewCashRegister { name: "Caja 1".to_string() }).unwrap();
+        register_queries::open_session(&conn, user_id, &OpenRegisterSession { register_id, opening_float: 500.0 }).unwrap();
+        let product_id = product_queries::create_product(&conn, user_id, NewProduct {
+            name: "Taza".to_string(),
+            code: "TZ-1".to_string(),
+            price: 4.0,
+            stock: 10.0,
+            allow_backorder: false,
+            description: None,
+            plu: None,
+            unit: "pza".to_string(),
+            quantity_decimals: 0,
+            category_id: None,
+        }).unwrap();
+
+        let sale_id = draft(&conn);
+        sale_detail_queries::create_sale_detail(&conn, user_id, NewSaleDetail {
+            sale_id,
+            product_id,
+            quantity: 1.0,
+            unit_price: 4.0,
+            discount: 0.0,
+            tax_percentage: 16.0,
+        }).unwrap();
+        sale_queries::complete_sale(&conn, user_id, sale_id, &[NewSalePayment {
+            method: PaymentMethod::Cash,
+            amount: 5.0,
+            reference: None,
+            card_last4: None,
+            authorization_code: None,
+        }]).unwrap();
+
+        let canceled = draft(&conn);
+        sale_queries::cancel_sale(&conn, user_id, canceled, "Cliente se retiró").unwrap();
+        let verification = verify_chain(&conn).unwrap();
+        assert!(verification.valid, "{:?}", verification.reason);
+        assert_eq!(verification.sealed_sales, 1);
+
+        // Una venta cobrada que se saltó el sellado sí rompe la cadena
+        let skipped = draft(&conn);
+        conn.execute(
+            "UPDATE sales SET status = 'completed', session_id = (SELECT MAX(id) FROM register_sessions) WHERE id = ?",
+            [skipped],
+        ).unwrap();
+        let verification = verify_chain(&conn).unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.first_broken_sale_id, Some(skipped));
+    }
+}
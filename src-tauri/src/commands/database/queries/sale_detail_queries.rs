@@ -1,13 +1,17 @@
 use rusqlite::{params, Connection, Result};
+use super::super::connection::rebuild_table_if_outdated;
+use super::super::errors::validation_error;
 use super::super::models::sale_detail::{SaleDetail, NewSaleDetail, SaleDetailWithProduct};
+use super::super::models::inventory::{MovementType, NewInventoryMovement};
+use super::{bundle_queries, product_queries, sale_queries, settings_queries};
 
-pub fn create_table(conn: &Connection) -> Result<()> {
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS sale_details (
+fn sale_details_table_sql(name: &str) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             sale_id INTEGER NOT NULL,
             product_id INTEGER NOT NULL,
-            quantity INTEGER NOT NULL CHECK(quantity > 0),
+            quantity REAL NOT NULL CHECK(quantity > 0),
             unit_price REAL NOT NULL CHECK(unit_price >= 0),
             subtotal REAL NOT NULL CHECK(subtotal >= 0),
             discount REAL DEFAULT 0 CHECK(discount >= 0),
@@ -15,14 +19,58 @@ pub fn create_table(conn: &Connection) -> Result<()> {
             FOREIGN KEY (sale_id) REFERENCES sales(id) ON DELETE CASCADE,
             FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE RESTRICT
         )",
-        [],
-    )?;
+        name
+    )
+}
+
+pub fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(&sale_details_table_sql("sale_details"), [])?;
+    rebuild_table_if_outdated(conn, "sale_details", "quantity REAL", sale_details_table_sql)?;
     Ok(())
 }
 
-pub fn create_sale_detail(conn: &Connection, detail: NewSaleDetail) -> Result<i32> {
-    let subtotal = (detail.unit_price * detail.quantity as f64) - detail.discount;
-    let tax_amount = subtotal * (detail.tax_percentage / 100.0);
+pub fn create_sale_detail(conn: &Connection, user_id: i32, detail: NewSaleDetail) -> Result<i32> {
+    sale_queries::ensure_draft(conn, detail.sale_id)?;
+    let product = product_queries::get_product(conn, detail.product_id)?;
+    product.validate_quantity(detail.quantity).map_err(validation_error)?;
+
+    let amount = (detail.unit_price * detail.quantity) - detail.discount;
+    insert_detail(conn, user_id, &detail, amount)
+}
+
+// Línea a partir de una lectura del escáner con el precio de lista y el
+// impuesto configurado. En etiquetas de báscula con precio el importe de la
+// línea es el impreso: la cantidad se redondea a gramos y multiplicada por el
+// precio no siempre da lo mismo que cobró la báscula.
+pub fn create_scanned_sale_detail(conn: &Connection, user_id: i32, sale_id: i32, code: &str) -> Result<i32> {
+    let Some(scanned) = product_queries::find_product_by_code(conn, code)? else {
+        return Err(validation_error(format!("No hay ningún producto con el código {}", code.trim())));
+    };
+    let detail = NewSaleDetail {
+        sale_id,
+        product_id: scanned.product.id,
+        quantity: scanned.quantity,
+        unit_price: scanned.product.price,
+        discount: 0.0,
+        tax_percentage: settings_queries::get_tax_settings(conn)?.tax_percentage,
+    };
+    sale_queries::ensure_draft(conn, sale_id)?;
+    scanned.product.validate_quantity(detail.quantity).map_err(validation_error)?;
+
+    let amount = scanned.embedded_price.unwrap_or(detail.unit_price * detail.quantity);
+    insert_detail(conn, user_id, &detail, amount)
+}
+
+// `amount` es el importe de la línea ya con descuento. Con precios con
+// impuesto incluido ese importe es lo que paga el cliente y el impuesto se
+// separa de él; si no, se agrega encima.
+fn insert_detail(conn: &Connection, user_id: i32, detail: &NewSaleDetail, amount: f64) -> Result<i32> {
+    let (subtotal, tax_amount) = if settings_queries::get_tax_settings(conn)?.prices_include_tax {
+        let tax_amount = amount * detail.tax_percentage / (100.0 + detail.tax_percentage);
+        (amount - tax_amount, tax_amount)
+    } else {
+        (amount, amount * (detail.tax_percentage / 100.0))
+    };
     
     conn.execute(
         "INSERT INTO sale_details 
@@ -38,14 +86,39 @@ pub fn create_sale_detail(conn: &Connection, detail: NewSaleDetail) -> Result<i3
             tax_amount
         ],
     )?;
+    let id = conn.last_insert_rowid() as i32;
     
-    // Actualizar stock del producto
-    conn.execute(
-        "UPDATE products SET stock = stock - ? WHERE id = ?",
-        params![detail.quantity, detail.product_id],
-    )?;
+    // Salida de inventario ligada a la venta; un paquete descuenta sus componentes
+    bundle_queries::record_sale_line(conn, Some(user_id), id, &NewInventoryMovement {
+        product_id: detail.product_id,
+        movement_type: MovementType::Sale,
+        quantity: -detail.quantity,
+        reason: None,
+        reference_type: Some("sale".to_string()),
+        reference_id: Some(detail.sale_id),
+    })?;
     
-    Ok(conn.last_insert_rowid() as i32)
+    Ok(id)
+}
+
+pub fn get_sale_detail(conn: &Connection, id: i32) -> Result<SaleDetail> {
+    conn.query_row(
+        "SELECT id, sale_id, product_id, quantity, unit_price, subtotal, discount, tax_amount 
+        FROM sale_details WHERE id = ?",
+        [id],
+        |row| {
+            Ok(SaleDetail {
+                id: row.get(0)?,
+                sale_id: row.get(1)?,
+                product_id: row.get(2)?,
+                quantity: row.get(3)?,
+                unit_price: row.get(4)?,
+                subtotal: row.get(5)?,
+                discount: row.get(6)?,
+                tax_amount: row.get(7)?,
+            })
+        },
+    )
 }
 
 pub fn get_details_by_sale(conn: &Connection, sale_id: i32) -> Result<Vec<SaleDetail>> {
@@ -82,6 +155,7 @@ pub fn get_details_with_products_by_sale(
             sd.product_id, 
             p.name as product_name,
             p.code as product_code,
+            p.unit,
             sd.quantity, 
             sd.unit_price, 
             sd.subtotal, 
@@ -99,11 +173,12 @@ pub fn get_details_with_products_by_sale(
             product_id: row.get(2)?,
             product_name: row.get(3)?,
             product_code: row.get(4)?,
-            quantity: row.get(5)?,
-            unit_price: row.get(6)?,
-            subtotal: row.get(7)?,
-            discount: row.get(8)?,
-            tax_amount: row.get(9)?,
+            unit: row.get(5)?,
+            quantity: row.get(6)?,
+            unit_price: row.get(7)?,
+            subtotal: row.get(8)?,
+            discount: row.get(9)?,
+            tax_amount: row.get(10)?,
         })
     })?
     .collect::<Result<Vec<_>>>()?;
@@ -111,22 +186,130 @@ pub fn get_details_with_products_by_sale(
     Ok(details)
 }
 
-pub fn delete_sale_detail(conn: &Connection, id: i32) -> Result<()> {
+pub fn delete_sale_detail(conn: &Connection, user_id: i32, id: i32) -> Result<()> {
     // Primero obtenemos el detalle para actualizar el stock
     let detail = conn.query_row(
-        "SELECT product_id, quantity FROM sale_details WHERE id = ?",
+        "SELECT product_id, quantity, sale_id FROM sale_details WHERE id = ?",
         [id],
-        |row| Ok((row.get::<_, i32>(0)?, row.get::<_, i32>(1)?))
+        |row| Ok((row.get::<_, i32>(0)?, row.get::<_, f64>(1)?, row.get::<_, i32>(2)?))
     )?;
+    sale_queries::ensure_draft(conn, detail.2)?;
+    
+    // Revertimos el stock con los componentes guardados antes de borrarlos
+    bundle_queries::reverse_sale_line(conn, Some(user_id), id, &NewInventoryMovement {
+        product_id: detail.0,
+        movement_type: MovementType::SaleVoid,
+        quantity: detail.1,
+        reason: None,
+        reference_type: Some("sale".to_string()),
+        reference_id: Some(detail.2),
+    })?;
     
     // Eliminamos el detalle
+    bundle_queries::delete_sale_line(conn, id)?;
     conn.execute("DELETE FROM sale_details WHERE id = ?", [id])?;
     
-    // Revertimos el stock
-    conn.execute(
-        "UPDATE products SET stock = stock + ? WHERE id = ?",
-        params![detail.1, detail.0],
-    )?;
-    
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::connection::create_tables;
+    use super::super::super::models::product::{gtin_check_digit, NewProduct};
+    use super::super::super::models::sale::NewSale;
+    use super::super::super::models::settings::TaxSettings;
+    use super::super::super::models::user::{NewUser, UserRole};
+    use super::super::user_queries;
+
+    #[test]
+    fn price_labels_keep_the_printed_amount() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        let user_id = user_queries::create_user(&conn, NewUser {
+            username: "cajero".to_string(),
+            email: "cajero@example.com".to_string(),
+            password: "Secreto123".to_string(),
+            role: UserRole::Seller,
+            full_name: "Cajero".to_string(),
+        }).unwrap();
+        let product_id = product_queries::create_product(&conn, user_id, NewProduct {
+            name: "Queso".to_string(),
+            code: "DELI-456".to_string(),
+            price: 179.99,
+            stock: 0.0,
+            allow_backorder: true,
+            description: None,
+            plu: Some("456".to_string()),
+            unit: "kg".to_string(),
+            quantity_decimals: 3,
+            category_id: None,
+        }).unwrap();
+        let sale_id = sale_queries::create_sale(&conn, NewSale {
+            customer_id: None,
+            subtotal: 0.0,
+            taxes: 0.0,
+            total: 0.0,
+            payment_method: "cash".to_string(),
+        }).unwrap();
+
+        // La cantidad se redondea a 0.250 kg, que a 179.99 daría 45.00; la
+        // báscula cobró 45.01 y eso es lo que queda en la línea
+        let label = format!("220045604501{}", gtin_check_digit("220045604501").unwrap());
+        let id = create_scanned_sale_detail(&conn, user_id, sale_id, &label).unwrap();
+        let detail = get_sale_detail(&conn, id).unwrap();
+        assert_eq!((detail.product_id, detail.quantity, detail.unit_price), (product_id, 0.25, 179.99));
+        assert_eq!(detail.subtotal, 45.01);
+
+        let id = create_scanned_sale_detail(&conn, user_id, sale_id, "DELI-456").unwrap();
+        assert_eq!(get_sale_detail(&conn, id).unwrap().subtotal, 179.99);
+        assert!(create_scanned_sale_detail(&conn, user_id, sale_id, "NO-EXISTE").is_err());
+    }
+
+    #[test]
+    fn included_tax_is_split_out_of_the_line_amount() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        settings_queries::save_tax_settings(&conn, &TaxSettings {
+            tax_percentage: 16.0,
+            prices_include_tax: true,
+        }).unwrap();
+        let user_id = user_queries::create_user(&conn, NewUser {
+            username: "cajero".to_string(),
+            email: "cajero@example.com".to_string(),
+            password: "Secreto123".to_string(),
+            role: UserRole::Seller,
+            full_name: "Cajero".to_string(),
+        }).unwrap();
+        product_queries::create_product(&conn, user_id, NewProduct {
+            name: "Queso".to_string(),
+            code: "DELI-456".to_string(),
+            price: 116.0,
+            stock: 0.0,
+            allow_backorder: true,
+            description: None,
+            plu: Some("456".to_string()),
+            unit: "kg".to_string(),
+            quantity_decimals: 3,
+            category_id: None,
+        }).unwrap();
+        let sale_id = sale_queries::create_sale(&conn, NewSale {
+            customer_id: None,
+            subtotal: 0.0,
+            taxes: 0.0,
+            total: 0.0,
+            payment_method: "cash".to_string(),
+        }).unwrap();
+
+        let id = create_scanned_sale_detail(&conn, user_id, sale_id, "DELI-456").unwrap();
+        let detail = get_sale_detail(&conn, id).unwrap();
+        assert!((detail.subtotal - 100.0).abs() < 1e-9);
+        assert!((detail.tax_amount - 16.0).abs() < 1e-9);
+
+        // La etiqueta con precio también trae el impuesto incluido
+        let label = format!("220045611600{}", gtin_check_digit("220045611600").unwrap());
+        let id = create_scanned_sale_detail(&conn, user_id, sale_id, &label).unwrap();
+        let detail = get_sale_detail(&conn, id).unwrap();
+        assert!((detail.subtotal - 100.0).abs() < 1e-9);
+        assert!((detail.tax_amount - 16.0).abs() < 1e-9);
+    }
+}
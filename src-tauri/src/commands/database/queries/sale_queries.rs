@@ -1,10 +1,15 @@
 use rusqlite::{Connection, params, Result};
 use chrono::NaiveDateTime;
-use super::super::models::sale::{Sale, NewSale};
+use super::super::connection::{add_column_if_missing, rebuild_table_if_outdated};
+use super::super::errors::validation_error;
+use super::super::models::sale::{Sale, NewSale, SaleStatus};
+use super::super::models::payment::{NewSalePayment, SaleCompletion};
+use super::super::models::inventory::{MovementType, NewInventoryMovement};
+use super::{bundle_queries, payment_queries, register_queries, report_queries, sale_chain_queries, sale_detail_queries};
 
-pub fn create_table(conn: &Connection) -> Result<()> {
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS sales (
+fn sales_table_sql(name: &str) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             date DATETIME DEFAULT CURRENT_TIMESTAMP,
             customer_id INTEGER,
@@ -12,33 +17,140 @@ pub fn create_table(conn: &Connection) -> Result<()> {
             taxes REAL NOT NULL,
             total REAL NOT NULL,
             payment_method TEXT CHECK(payment_method IN ('cash', 'credit', 'debit', 'transfer')),
-            status TEXT DEFAULT 'completed' CHECK(status IN ('completed', 'canceled', 'refunded')),
+            status TEXT DEFAULT 'draft' CHECK(status IN ('draft', 'completed', 'canceled', 'refunded')),
+            chain_seq INTEGER,
+            previous_hash TEXT,
+            hash TEXT,
+            sealed_at DATETIME,
+            cancel_reason TEXT,
+            canceled_by INTEGER,
+            canceled_at DATETIME,
             FOREIGN KEY (customer_id) REFERENCES customers(id) ON DELETE SET NULL
         )",
+        name
+    )
+}
+
+pub fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(&sales_table_sql("sales"), [])?;
+    add_column_if_missing(conn, "sales", "chain_seq", "INTEGER")?;
+    add_column_if_missing(conn, "sales", "previous_hash", "TEXT")?;
+    add_column_if_missing(conn, "sales", "hash", "TEXT")?;
+    add_column_if_missing(conn, "sales", "sealed_at", "DATETIME")?;
+    add_column_if_missing(conn, "sales", "cancel_reason", "TEXT")?;
+    add_column_if_missing(conn, "sales", "canceled_by", "INTEGER")?;
+    add_column_if_missing(conn, "sales", "canceled_at", "DATETIME")?;
+    // Instalaciones anteriores no aceptan el estado 'draft'
+    rebuild_table_if_outdated(conn, "sales", "'draft'", sales_table_sql)?;
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_sales_chain_seq ON sales(chain_seq)",
         [],
     )?;
     Ok(())
 }
 
+// Toda venta nace como borrador; solo los borradores admiten cambios en sus líneas.
 pub fn create_sale(conn: &Connection, sale: NewSale) -> Result<i32> {
     conn.execute(
         "INSERT INTO sales (customer_id, subtotal, taxes, total, payment_method, status) 
-        VALUES (?, ?, ?, ?, ?, ?)",
+        VALUES (?, ?, ?, ?, ?, 'draft')",
         params![
             sale.customer_id, 
             sale.subtotal, 
             sale.taxes, 
             sale.total, 
-            sale.payment_method, 
-            sale.status.unwrap_or_else(|| "completed".to_string())
+            sale.payment_method
         ],
     )?;
     Ok(conn.last_insert_rowid() as i32)
 }
 
+pub fn get_status(conn: &Connection, id: i32) -> Result<SaleStatus> {
+    let status: String = conn.query_row("SELECT status FROM sales WHERE id = ?", [id], |row| row.get(0))?;
+    Ok(SaleStatus::from(status.as_str()))
+}
+
+pub fn ensure_draft(conn: &Connection, id: i32) -> Result<()> {
+    if get_status(conn, id)? != SaleStatus::Draft {
+        return Err(validation_error(format!(
+            "La venta {} ya no es un borrador; use una devolución o nota de crédito para modificarla",
+            id
+        )));
+    }
+    Ok(())
+}
+
+fn transition(conn: &Connection, id: i32, next: SaleStatus) -> Result<()> {
+    let current = get_status(conn, id)?;
+    if !current.can_transition_to(next) {
+        return Err(validation_error(format!(
+            "La venta {} no puede pasar de '{}' a '{}'",
+            id, current, next
+        )));
+    }
+    conn.execute(
+        "UPDATE sales SET status = ? WHERE id = ?",
+        params![next.to_string(), id],
+    )?;
+    // El sellado registra la venta completada; aquí solo los cambios posteriores
+    if next != SaleStatus::Completed {
+        sale_chain_queries::record_status(conn, id, &next.to_string())?;
+    }
+    Ok(())
+}
+
+// Cierra el borrador: los totales se recalculan a partir de las líneas, se
+// registran los pagos y la venta queda sellada en la cadena de integridad.
+pub fn complete_sale(
+    conn: &Connection,
+    user_id: i32,
+    id: i32,
+    payments: &[NewSalePayment],
+) -> Result<SaleCompletion> {
+    ensure_draft(conn, id)?;
+    let session_id = register_queries::require_open_session(conn, user_id)?;
+    // La fecha de la venta es la del cobro, no la de cuando se abrió el borrador
+    conn.execute("UPDATE sales SET date = CURRENT_TIMESTAMP WHERE id = ?", [id])?;
+    report_queries::ensure_day_open(
+        conn,
+        register_queries::get_session(conn, session_id)?.register_id,
+        &report_queries::sale_business_date(conn, id)?,
+    )?;
+    let (lines, subtotal, taxes): (i64, f64, f64) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(subtotal), 0), COALESCE(SUM(tax_amount), 0)
+        FROM sale_details WHERE sale_id = ?",
+        [id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+    if lines == 0 {
+        return Err(validation_error("No se puede completar una venta sin productos"));
+    }
+
+    let total = subtotal + taxes;
+    let change_due = payment_queries::record_payments(conn, id, total, payments)?;
+    let payment_method = payment_queries::primary_method(conn, id)?;
+
+    conn.execute(
+        "UPDATE sales SET subtotal = ?, taxes = ?, total = ?, payment_method = ?, session_id = ?
+        WHERE id = ?",
+        params![subtotal, taxes, total, payment_method.to_string(), session_id, id],
+    )?;
+    transition(conn, id, SaleStatus::Completed)?;
+    let seal = sale_chain_queries::seal_sale(conn, id)?;
+
+    Ok(SaleCompletion {
+        seal,
+        total,
+        tendered: payments.iter().map(|payment| payment.amount).sum(),
+        change_due,
+        payments: payment_queries::get_payments_by_sale(conn, id)?,
+    })
+}
+
 pub fn get_sale(conn: &Connection, id: i32) -> Result<Sale> {
     conn.query_row(
-        "SELECT id, date, customer_id, subtotal, taxes, total, payment_method, status 
+        "SELECT id, date, customer_id, subtotal, taxes, total, payment_method, status,
+        cancel_reason, canceled_by, canceled_at
         FROM sales WHERE id = ?",
         [id],
         |row| {
@@ -59,6 +171,9 @@ pub fn get_sale(conn: &Connection, id: i32) -> Result<Sale> {
                 total: row.get(5)?,
                 payment_method: row.get(6)?,
                 status: row.get(7)?,
+                cancel_reason: row.get(8)?,
+                canceled_by: row.get(9)?,
+                canceled_at: row.get(10)?,
             })
         },
     )
@@ -70,7 +185,8 @@ pub fn get_sales_by_date_range(
     end: NaiveDateTime
 ) -> Result<Vec<Sale>> {
     let mut stmt = conn.prepare(
-        "SELECT id, date, customer_id, subtotal, taxes, total, payment_method, status 
+        "SELECT id, date, customer_id, subtotal, taxes, total, payment_method, status,
+        cancel_reason, canceled_by, canceled_at
         FROM sales 
         WHERE date BETWEEN ? AND ?
         ORDER BY date DESC"
@@ -102,6 +218,9 @@ pub fn get_sales_by_date_range(
                 total: row.get(5)?,
                 payment_method: row.get(6)?,
                 status: row.get(7)?,
+                cancel_reason: row.get(8)?,
+                canceled_by: row.get(9)?,
+                canceled_at: row.get(10)?,
             })
         }
     )?
@@ -110,10 +229,71 @@ pub fn get_sales_by_date_range(
     Ok(sales)
 }
 
-pub fn cancel_sale(conn: &Connection, id: i32) -> Result<()> {
-    conn.execute(
-        "UPDATE sales SET status = 'canceled' WHERE id = ?",
+pub fn mark_refunded(conn: &Connection, id: i32) -> Result<()> {
+    transition(conn, id, SaleStatus::Refunded)
+}
+
+// Cancela la venta y regresa al inventario lo que descontaron sus líneas. Una
+// venta facturada requiere cancelar antes la factura.
+pub fn cancel_sale(conn: &Connection, user_id: i32, id: i32, reason: &str) -> Result<()> {
+    if reason.trim().is_empty() {
+        return Err(validation_error("Indique el motivo de la cancelación"));
+    }
+
+    let active_invoices: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM invoices WHERE sale_id = ? AND status = 'active'",
         [id],
+        |row| row.get(0),
+    )?;
+    if active_invoices > 0 {
+        return Err(validation_error(format!(
+            "La venta {} tiene una factura activa; cancele la factura antes de cancelar la venta",
+            id
+        )));
+    }
+
+    let returns: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM returns WHERE sale_id = ?",
+        [id],
+        |row| row.get(0),
+    )?;
+    if returns > 0 {
+        return Err(validation_error(format!(
+            "La venta {} tiene devoluciones registradas; no se puede cancelar",
+            id
+        )));
+    }
+
+    let session_id: Option<i32> = conn.query_row(
+        "SELECT session_id FROM sales WHERE id = ?",
+        [id],
+        |row| row.get(0),
+    )?;
+    if let Some(session_id) = session_id {
+        report_queries::ensure_day_open(
+            conn,
+            register_queries::get_session(conn, session_id)?.register_id,
+            &report_queries::sale_business_date(conn, id)?,
+        )?;
+    }
+
+    transition(conn, id, SaleStatus::Canceled)?;
+
+    for detail in sale_detail_queries::get_details_by_sale(conn, id)? {
+        bundle_queries::reverse_sale_line(conn, Some(user_id), detail.id, &NewInventoryMovement {
+            product_id: detail.product_id,
+            movement_type: MovementType::Cancellation,
+            quantity: detail.quantity,
+            reason: Some(reason.trim().to_string()),
+            reference_type: Some("sale".to_string()),
+            reference_id: Some(id),
+        })?;
+    }
+
+    conn.execute(
+        "UPDATE sales SET cancel_reason = ?, canceled_by = ?, canceled_at = CURRENT_TIMESTAMP
+        WHERE id = ?",
+        params![reason.trim(), user_id, id],
     )?;
     Ok(())
-}
\ No newline at end of file
+}
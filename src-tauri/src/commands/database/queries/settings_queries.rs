@@ -0,0 +1,132 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use super::super::models::settings::{BusinessSettings, TaxSettings, PasswordPolicy, TwoFactorPolicy, PosRestrictions, InventorySettings, PrinterSettings, ScaleBarcodeSettings};
+
+const BUSINESS_KEY: &str = "business";
+const TAX_KEY: &str = "tax";
+const PASSWORD_POLICY_KEY: &str = "password_policy";
+const TWO_FACTOR_POLICY_KEY: &str = "two_factor_policy";
+const POS_RESTRICTIONS_KEY: &str = "pos_restrictions";
+const INVENTORY_KEY: &str = "inventory";
+const PRINTER_KEY: &str = "printer";
+const SCALE_BARCODES_KEY: &str = "scale_barcodes";
+const SETUP_COMPLETED_KEY: &str = "setup_completed_at";
+
+pub fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+pub fn get_setting(conn: &Connection, key: &str) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT value FROM settings WHERE key = ?",
+        [key],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+pub fn set_setting(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO settings (key, value) VALUES (?, ?)
+        ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+// Las secciones de configuración se guardan como JSON bajo una sola clave;
+// si no existen se usan los valores por defecto del modelo.
+pub fn get_section<T: DeserializeOwned + Default>(conn: &Connection, key: &str) -> Result<T> {
+    match get_setting(conn, key)? {
+        Some(value) => serde_json::from_str(&value)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))),
+        None => Ok(T::default()),
+    }
+}
+
+pub fn set_section<T: Serialize>(conn: &Connection, key: &str, section: &T) -> Result<()> {
+    let value = serde_json::to_string(section)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    set_setting(conn, key, &value)
+}
+
+pub fn get_business_settings(conn: &Connection) -> Result<BusinessSettings> {
+    get_section(conn, BUSINESS_KEY)
+}
+
+pub fn save_business_settings(conn: &Connection, settings: &BusinessSettings) -> Result<()> {
+    set_section(conn, BUSINESS_KEY, settings)
+}
+
+pub fn get_tax_settings(conn: &Connection) -> Result<TaxSettings> {
+    get_section(conn, TAX_KEY)
+}
+
+pub fn save_tax_settings(conn: &Connection, settings: &TaxSettings) -> Result<()> {
+    set_section(conn, TAX_KEY, settings)
+}
+
+pub fn get_password_policy(conn: &Connection) -> Result<PasswordPolicy> {
+    get_section(conn, PASSWORD_POLICY_KEY)
+}
+
+pub fn save_password_policy(conn: &Connection, policy: &PasswordPolicy) -> Result<()> {
+    set_section(conn, PASSWORD_POLICY_KEY, policy)
+}
+
+pub fn get_two_factor_policy(conn: &Connection) -> Result<TwoFactorPolicy> {
+    get_section(conn, TWO_FACTOR_POLICY_KEY)
+}
+
+pub fn save_two_factor_policy(conn: &Connection, policy: &TwoFactorPolicy) -> Result<()> {
+    set_section(conn, TWO_FACTOR_POLICY_KEY, policy)
+}
+
+pub fn get_pos_restrictions(conn: &Connection) -> Result<PosRestrictions> {
+    get_section(conn, POS_RESTRICTIONS_KEY)
+}
+
+pub fn save_pos_restrictions(conn: &Connection, restrictions: &PosRestrictions) -> Result<()> {
+    set_section(conn, POS_RESTRICTIONS_KEY, restrictions)
+}
+
+pub fn get_inventory_settings(conn: &Connection) -> Result<InventorySettings> {
+    get_section(conn, INVENTORY_KEY)
+}
+
+pub fn save_inventory_settings(conn: &Connection, settings: &InventorySettings) -> Result<()> {
+    set_section(conn, INVENTORY_KEY, settings)
+}
+
+pub fn get_printer_settings(conn: &Connection) -> Result<PrinterSettings> {
+    get_section(conn, PRINTER_KEY)
+}
+
+pub fn save_printer_settings(conn: &Connection, settings: &PrinterSettings) -> Result<()> {
+    set_section(conn, PRINTER_KEY, settings)
+}
+
+pub fn get_scale_barcode_settings(conn: &Connection) -> Result<ScaleBarcodeSettings> {
+    get_section(conn, SCALE_BARCODES_KEY)
+}
+
+pub fn save_scale_barcode_settings(conn: &Connection, settings: &ScaleBarcodeSettings) -> Result<()> {
+    set_section(conn, SCALE_BARCODES_KEY, settings)
+}
+
+pub fn is_provisioned(conn: &Connection) -> Result<bool> {
+    Ok(get_setting(conn, SETUP_COMPLETED_KEY)?.is_some())
+}
+
+pub fn mark_provisioned(conn: &Connection) -> Result<()> {
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    set_setting(conn, SETUP_COMPLETED_KEY, &now)
+}
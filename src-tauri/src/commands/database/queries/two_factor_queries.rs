@@ -0,0 +1,258 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use rand::Rng;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use sha2::{Digest, Sha256};
+use super::super::errors::validation_error;
+use super::super::models::two_factor::{TwoFactorEnrollment, TwoFactorStatus};
+use super::super::models::user::{AuthResult, User, UserRole};
+use super::{settings_queries, user_queries};
+use crate::utils::totp;
+
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const MAX_CHALLENGE_ATTEMPTS: i32 = 5;
+
+pub fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS user_two_factor (
+            user_id INTEGER PRIMARY KEY,
+            secret TEXT NOT NULL,
+            enabled BOOLEAN DEFAULT 0,
+            last_used_step INTEGER DEFAULT 0,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            confirmed_at DATETIME,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS recovery_codes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id INTEGER NOT NULL,
+            code_hash TEXT NOT NULL,
+            used_at DATETIME,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS login_challenges (
+            token TEXT PRIMARY KEY,
+            user_id INTEGER NOT NULL,
+            attempts INTEGER DEFAULT 0,
+            expires_at DATETIME NOT NULL,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    Sha256::digest(normalized.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub fn is_enabled(conn: &Connection, user_id: i32) -> Result<bool> {
+    let enabled: Option<bool> = conn
+        .query_row(
+            "SELECT enabled FROM user_two_factor WHERE user_id = ?",
+            [user_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(enabled.unwrap_or(false))
+}
+
+pub fn is_required(conn: &Connection, role: UserRole) -> Result<bool> {
+    let policy = settings_queries::get_two_factor_policy(conn)?;
+    Ok(role.is_supervisor() && policy.required_roles.contains(&role))
+}
+
+pub fn get_status(conn: &Connection, user: &User) -> Result<TwoFactorStatus> {
+    let recovery_codes_remaining = conn.query_row(
+        "SELECT COUNT(*) FROM recovery_codes WHERE user_id = ? AND used_at IS NULL",
+        [user.id],
+        |row| row.get(0),
+    )?;
+    Ok(TwoFactorStatus {
+        enabled: is_enabled(conn, user.id)?,
+        required: is_required(conn, user.role)?,
+        recovery_codes_remaining,
+    })
+}
+
+// Genera un secreto pendiente de confirmar; reemplaza cualquier inscripción
+// previa que no se haya completado.
+pub fn begin_enrollment(conn: &Connection, user: &User) -> Result<TwoFactorEnrollment> {
+    if !user.role.is_supervisor() {
+        return Err(validation_error("La verificación en dos pasos solo está disponible para administradores y gerentes"));
+    }
+    if is_enabled(conn, user.id)? {
+        return Err(validation_error("El usuario ya tiene activa la verificación en dos pasos"));
+    }
+
+    let secret = totp::generate_secret();
+    conn.execute(
+        "INSERT OR REPLACE INTO user_two_factor (user_id, secret, enabled) VALUES (?, ?, 0)",
+        params![user.id, secret],
+    )?;
+
+    let business = settings_queries::get_business_settings(conn)?;
+    let issuer = if business.business_name.trim().is_empty() {
+        "SaleSys".to_string()
+    } else {
+        business.business_name
+    };
+
+    Ok(TwoFactorEnrollment {
+        otpauth_uri: totp::otpauth_uri(&issuer, &user.username, &secret),
+        secret,
+    })
+}
+
+pub fn confirm_enrollment(conn: &Connection, user_id: i32, code: &str) -> Result<Vec<String>> {
+    let secret: Option<String> = conn
+        .query_row(
+            "SELECT secret FROM user_two_factor WHERE user_id = ? AND enabled = 0",
+            [user_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let secret = secret.ok_or_else(|| validation_error("No hay una inscripción pendiente de confirmar"))?;
+
+    let step = totp::verify_code(&secret, code, unix_now())
+        .ok_or_else(|| validation_error("Código de verificación inválido"))?;
+
+    conn.execute(
+        "UPDATE user_two_factor SET enabled = 1, last_used_step = ?, confirmed_at = CURRENT_TIMESTAMP
+        WHERE user_id = ?",
+        params![step as i64, user_id],
+    )?;
+    generate_recovery_codes(conn, user_id)
+}
+
+// Los códigos se muestran una sola vez; solo se guarda su hash.
+pub fn generate_recovery_codes(conn: &Connection, user_id: i32) -> Result<Vec<String>> {
+    conn.execute("DELETE FROM recovery_codes WHERE user_id = ?", [user_id])?;
+
+    let mut rng = rand::thread_rng();
+    let mut codes = Vec::with_capacity(RECOVERY_CODE_COUNT);
+    for _ in 0..RECOVERY_CODE_COUNT {
+        let raw: String = (0..8)
+            .map(|_| RECOVERY_CODE_CHARS[rng.gen_range(0..RECOVERY_CODE_CHARS.len())] as char)
+            .collect();
+        let code = format!("{}-{}", &raw[..4], &raw[4..]);
+        conn.execute(
+            "INSERT INTO recovery_codes (user_id, code_hash) VALUES (?, ?)",
+            params![user_id, hash_recovery_code(&code)],
+        )?;
+        codes.push(code);
+    }
+    Ok(codes)
+}
+
+// Acepta un código TOTP (sin permitir reutilizar el mismo paso de tiempo) o
+// un código de recuperación sin usar, que queda consumido.
+pub fn verify_user_code(conn: &Connection, user_id: i32, code: &str) -> Result<bool> {
+    let row: Option<(String, i64)> = conn
+        .query_row(
+            "SELECT secret, last_used_step FROM user_two_factor WHERE user_id = ? AND enabled = 1",
+            [user_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let (secret, last_used_step) = match row {
+        Some(row) => row,
+        None => return Ok(false),
+    };
+
+    if let Some(step) = totp::verify_code(&secret, code, unix_now()) {
+        if step as i64 > last_used_step {
+            conn.execute(
+                "UPDATE user_two_factor SET last_used_step = ? WHERE user_id = ?",
+                params![step as i64, user_id],
+            )?;
+            return Ok(true);
+        }
+        return Ok(false);
+    }
+
+    let used = conn.execute(
+        "UPDATE recovery_codes SET used_at = CURRENT_TIMESTAMP
+        WHERE user_id = ? AND code_hash = ? AND used_at IS NULL",
+        params![user_id, hash_recovery_code(code)],
+    )?;
+    Ok(used > 0)
+}
+
+pub fn disable(conn: &Connection, user_id: i32) -> Result<()> {
+    conn.execute("DELETE FROM user_two_factor WHERE user_id = ?", [user_id])?;
+    conn.execute("DELETE FROM recovery_codes WHERE user_id = ?", [user_id])?;
+    conn.execute("DELETE FROM login_challenges WHERE user_id = ?", [user_id])?;
+    Ok(())
+}
+
+// Segundo paso del inicio de sesión: tras validar la contraseña se decide si
+// el usuario entra directamente o debe presentar un código.
+pub fn login_result(conn: &Connection, user: User) -> Result<AuthResult> {
+    if is_enabled(conn, user.id)? {
+        conn.execute("DELETE FROM login_challenges WHERE expires_at < CURRENT_TIMESTAMP", [])?;
+        let challenge_token = uuid::Uuid::new_v4().to_string();
+        conn.execute(
+            "INSERT INTO login_challenges (token, user_id, expires_at)
+            VALUES (?, ?, datetime('now', '+5 minutes'))",
+            params![challenge_token, user.id],
+        )?;
+        return Ok(AuthResult::TwoFactorRequired { challenge_token });
+    }
+
+    if is_required(conn, user.role)? {
+        return Ok(AuthResult::TwoFactorSetupRequired { user });
+    }
+
+    Ok(AuthResult::Authenticated { user })
+}
+
+pub fn complete_login(conn: &Connection, challenge_token: &str, code: &str) -> Result<User> {
+    let challenge: Option<(i32, i32)> = conn
+        .query_row(
+            "SELECT user_id, attempts FROM login_challenges
+            WHERE token = ? AND expires_at >= CURRENT_TIMESTAMP",
+            [challenge_token],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let (user_id, attempts) = challenge
+        .ok_or_else(|| validation_error("La sesión de verificación expiró, vuelva a iniciar sesión"))?;
+
+    if attempts >= MAX_CHALLENGE_ATTEMPTS {
+        conn.execute("DELETE FROM login_challenges WHERE token = ?", [challenge_token])?;
+        return Err(validation_error("Demasiados intentos fallidos, vuelva a iniciar sesión"));
+    }
+
+    if !verify_user_code(conn, user_id, code)? {
+        conn.execute(
+            "UPDATE login_challenges SET attempts = attempts + 1 WHERE token = ?",
+            [challenge_token],
+        )?;
+        return Err(validation_error("Código de verificación inválido"));
+    }
+
+    conn.execute("DELETE FROM login_challenges WHERE token = ?", [challenge_token])?;
+    user_queries::get_user_by_id(conn, user_id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)
+}
@@ -1,6 +1,16 @@
 use bcrypt::{hash, verify};
-use rusqlite::{params, Connection, Result};
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use super::super::connection::add_column_if_missing;
+use super::super::errors::validation_error;
 use super::super::models::user::{User, NewUser, UserRole};
+use super::settings_queries;
+
+// Intentos fallidos de PIN o contraseña de supervisor antes de bloquearlo
+const MAX_SUPERVISOR_ATTEMPTS: i32 = 5;
+const SUPERVISOR_LOCKOUT_MINUTES: i32 = 15;
+
+const USER_COLUMNS: &str =
+    "id, username, email, password_hash, role, full_name, is_active, must_change_password";
 
 pub fn create_table(conn: &Connection) -> Result<()> {
     conn.execute(
@@ -11,20 +21,135 @@ pub fn create_table(conn: &Connection) -> Result<()> {
             password_hash TEXT NOT NULL,
             role TEXT NOT NULL CHECK(role IN ('admin', 'seller', 'manager')),
             full_name TEXT NOT NULL,
-            is_active BOOLEAN DEFAULT 1
+            is_active BOOLEAN DEFAULT 1,
+            must_change_password BOOLEAN DEFAULT 0,
+            password_changed_at DATETIME,
+            pin_hash TEXT,
+            pin_failed_attempts INTEGER DEFAULT 0,
+            pin_locked_until DATETIME
+        )",
+        [],
+    )?;
+    add_column_if_missing(conn, "users", "must_change_password", "BOOLEAN DEFAULT 0")?;
+    add_column_if_missing(conn, "users", "password_changed_at", "DATETIME")?;
+    add_column_if_missing(conn, "users", "pin_hash", "TEXT")?;
+    add_column_if_missing(conn, "users", "pin_failed_attempts", "INTEGER DEFAULT 0")?;
+    add_column_if_missing(conn, "users", "pin_locked_until", "DATETIME")?;
+    conn.execute(
+        "UPDATE users SET password_changed_at = CURRENT_TIMESTAMP WHERE password_changed_at IS NULL",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS password_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id INTEGER NOT NULL,
+            password_hash TEXT NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
         )",
         [],
     )?;
     Ok(())
 }
 
+fn row_to_user(row: &Row) -> Result<User> {
+    Ok(User {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        email: row.get(2)?,
+        password_hash: row.get(3)?,
+        role: UserRole::from(row.get::<_, String>(4)?.as_str()),
+        full_name: row.get(5)?,
+        is_active: row.get(6)?,
+        must_change_password: row.get(7)?,
+    })
+}
+
+fn hash_password(password: &str) -> Result<String> {
+    hash(password, 12)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+fn verify_password(password: &str, password_hash: &str) -> Result<bool> {
+    verify(password, password_hash)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+// Valida la contraseña contra la política configurada. Con `user_id` también
+// se revisa el historial para impedir reutilizar contraseñas recientes.
+pub fn check_password_policy(
+    conn: &Connection,
+    user_id: Option<i32>,
+    username: &str,
+    password: &str,
+) -> Result<()> {
+    let policy = settings_queries::get_password_policy(conn)?;
+    let mut problems = Vec::new();
+
+    if password.chars().count() < policy.min_length {
+        problems.push(format!("debe tener al menos {} caracteres", policy.min_length));
+    }
+    if policy.require_uppercase && !password.chars().any(|c| c.is_uppercase()) {
+        problems.push("debe incluir una letra mayúscula".to_string());
+    }
+    if policy.require_lowercase && !password.chars().any(|c| c.is_lowercase()) {
+        problems.push("debe incluir una letra minúscula".to_string());
+    }
+    if policy.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+        problems.push("debe incluir un número".to_string());
+    }
+    if policy.require_symbol && !password.chars().any(|c| !c.is_alphanumeric()) {
+        problems.push("debe incluir un símbolo".to_string());
+    }
+    if password.to_lowercase() == username.to_lowercase() {
+        problems.push("no puede ser igual al nombre de usuario".to_string());
+    }
+
+    if let Some(user_id) = user_id {
+        if policy.history_size > 0 {
+            let mut stmt = conn.prepare(
+                "SELECT password_hash FROM password_history 
+                WHERE user_id = ? ORDER BY id DESC LIMIT ?"
+            )?;
+            let previous = stmt
+                .query_map(params![user_id, policy.history_size], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<_>>>()?;
+
+            for previous_hash in previous {
+                if verify_password(password, &previous_hash)? {
+                    problems.push(format!(
+                        "no puede repetir ninguna de las últimas {} contraseñas",
+                        policy.history_size
+                    ));
+                    break;
+                }
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(validation_error(format!("La contraseña {}", problems.join(", "))))
+    }
+}
+
+fn record_password_history(conn: &Connection, user_id: i32, password_hash: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO password_history (user_id, password_hash) VALUES (?, ?)",
+        params![user_id, password_hash],
+    )?;
+    Ok(())
+}
+
 pub fn create_user(conn: &Connection, user: NewUser) -> Result<i32> {
-    let password_hash = hash(&user.password, 12)
-        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))?;
+    check_password_policy(conn, None, &user.username, &user.password)?;
+    let password_hash = hash_password(&user.password)?;
     
     conn.execute(
-        "INSERT INTO users (username, email, password_hash, role, full_name) 
-        VALUES (?, ?, ?, ?, ?)",
+        "INSERT INTO users (username, email, password_hash, role, full_name, password_changed_at) 
+        VALUES (?, ?, ?, ?, ?, CURRENT_TIMESTAMP)",
         params![
             user.username, 
             user.email, 
@@ -33,7 +158,9 @@ pub fn create_user(conn: &Connection, user: NewUser) -> Result<i32> {
             user.full_name
         ],
     )?;
-    Ok(conn.last_insert_rowid() as i32)
+    let user_id = conn.last_insert_rowid() as i32;
+    record_password_history(conn, user_id, &password_hash)?;
+    Ok(user_id)
 }
 
 pub fn authenticate_user(
@@ -42,27 +169,19 @@ pub fn authenticate_user(
     password: &str
 ) -> Result<Option<User>> {
     let mut stmt = conn.prepare(
-        "SELECT id, username, email, password_hash, role, full_name, is_active 
-        FROM users WHERE username = ?"
+        &format!("SELECT {} FROM users WHERE username = ?", USER_COLUMNS)
     )?;
     
-    let mut rows = stmt.query_map([username], |row| {
-        Ok(User {
-            id: row.get(0)?,
-            username: row.get(1)?,
-            email: row.get(2)?,
-            password_hash: row.get(3)?,
-            role: UserRole::from(row.get::<_, String>(4)?.as_str()),
-            full_name: row.get(5)?,
-            is_active: row.get(6)?,
-        })
-    })?;
+    let mut rows = stmt.query_map([username], row_to_user)?;
     
     if let Some(user) = rows.next() {
-        let user = user?;
-        if verify(password, &user.password_hash)
-            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))?
-        {
+        let mut user = user?;
+        if verify_password(password, &user.password_hash)? {
+            // Una contraseña vencida se trata igual que un cambio obligatorio
+            if !user.must_change_password && password_expired(conn, user.id)? {
+                require_password_change(conn, user.id)?;
+                user.must_change_password = true;
+            }
             Ok(Some(user))
         } else {
             Ok(None)
@@ -72,21 +191,136 @@ pub fn authenticate_user(
     }
 }
 
+fn password_expired(conn: &Connection, user_id: i32) -> Result<bool> {
+    let policy = settings_queries::get_password_policy(conn)?;
+    match policy.max_age_days {
+        Some(days) => conn.query_row(
+            "SELECT COALESCE(password_changed_at <= datetime('now', ?), 1) FROM users WHERE id = ?",
+            params![format!("-{} days", days), user_id],
+            |row| row.get(0),
+        ),
+        None => Ok(false),
+    }
+}
+
+// `must_change_password` queda activo cuando un administrador restablece la
+// contraseña, de modo que el usuario deba elegir una propia al entrar.
 pub fn update_user_password(
     conn: &Connection, 
     user_id: i32, 
-    new_password: &str
+    new_password: &str,
+    must_change_password: bool,
 ) -> Result<()> {
-    let password_hash = hash(new_password, 12)
-        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))?;
+    let user = get_user_by_id(conn, user_id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+    check_password_policy(conn, Some(user_id), &user.username, new_password)?;
+    let password_hash = hash_password(new_password)?;
     
     conn.execute(
-        "UPDATE users SET password_hash = ? WHERE id = ?",
-        params![password_hash, user_id],
+        "UPDATE users SET password_hash = ?, must_change_password = ?, 
+        password_changed_at = CURRENT_TIMESTAMP WHERE id = ?",
+        params![password_hash, must_change_password, user_id],
     )?;
+    record_password_history(conn, user_id, &password_hash)?;
     Ok(())
 }
 
+// Reautenticación para operaciones sensibles sobre la propia cuenta
+pub fn verify_user_password(conn: &Connection, user_id: i32, password: &str) -> Result<bool> {
+    match get_user_by_id(conn, user_id)? {
+        Some(user) => verify_password(password, &user.password_hash),
+        None => Ok(false),
+    }
+}
+
+pub fn change_own_password(
+    conn: &Connection,
+    user_id: i32,
+    old_password: &str,
+    new_password: &str,
+) -> Result<()> {
+    let user = get_user_by_id(conn, user_id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+    if !verify_password(old_password, &user.password_hash)? {
+        return Err(validation_error("La contraseña actual es incorrecta"));
+    }
+    update_user_password(conn, user_id, new_password, false)
+}
+
+pub fn set_user_pin(conn: &Connection, user_id: i32, pin: &str) -> Result<()> {
+    let user = get_user_by_id(conn, user_id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+    if !user.role.is_supervisor() {
+        return Err(validation_error("Solo administradores y gerentes pueden tener PIN de autorización"));
+    }
+    if !(4..=8).contains(&pin.len()) || !pin.chars().all(|c| c.is_ascii_digit()) {
+        return Err(validation_error("El PIN debe tener entre 4 y 8 dígitos"));
+    }
+
+    conn.execute(
+        "UPDATE users SET pin_hash = ? WHERE id = ?",
+        params![hash_password(pin)?, user_id],
+    )?;
+    Ok(())
+}
+
+// Valida a un supervisor activo con su PIN o, si no tiene uno, con su contraseña.
+// Tras varios intentos fallidos el supervisor queda bloqueado unos minutos, de
+// modo que un PIN corto no pueda adivinarse desde el mostrador.
+pub fn verify_supervisor(conn: &Connection, username: &str, credential: &str) -> Result<Option<User>> {
+    let row: Option<(i32, String, Option<String>, bool)> = conn
+        .query_row(
+            "SELECT id, password_hash, pin_hash,
+                COALESCE(pin_locked_until > CURRENT_TIMESTAMP, 0)
+            FROM users WHERE username = ? AND is_active = 1",
+            [username],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()?;
+    let (user_id, password_hash, pin_hash, locked) = match row {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+    if locked {
+        return Err(validation_error(format!(
+            "Autorización bloqueada por intentos fallidos; intente de nuevo en {} minutos",
+            SUPERVISOR_LOCKOUT_MINUTES
+        )));
+    }
+
+    let valid = match pin_hash {
+        Some(pin_hash) => verify_password(credential, &pin_hash)?,
+        None => verify_password(credential, &password_hash)?,
+    };
+    if !valid {
+        conn.execute(
+            "UPDATE users SET
+                pin_locked_until = CASE WHEN pin_failed_attempts + 1 >= ?1
+                    THEN datetime('now', ?2) ELSE pin_locked_until END,
+                pin_failed_attempts = CASE WHEN pin_failed_attempts + 1 >= ?1
+                    THEN 0 ELSE pin_failed_attempts + 1 END
+            WHERE id = ?3",
+            params![MAX_SUPERVISOR_ATTEMPTS, format!("+{} minutes", SUPERVISOR_LOCKOUT_MINUTES), user_id],
+        )?;
+        return Ok(None);
+    }
+
+    conn.execute(
+        "UPDATE users SET pin_failed_attempts = 0, pin_locked_until = NULL WHERE id = ?",
+        [user_id],
+    )?;
+    Ok(get_user_by_id(conn, user_id)?.filter(|user| user.role.is_supervisor()))
+}
+
+pub fn require_password_change(conn: &Connection, user_id: i32) -> Result<()> {
+    conn.execute(
+        "UPDATE users SET must_change_password = 1 WHERE id = ?",
+        params![user_id],
+    )?;
+    Ok(())
+}
+
+pub fn count_users(conn: &Connection) -> Result<i64> {
+    conn.query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))
+}
+
 pub fn deactivate_user(conn: &Connection, user_id: i32) -> Result<()> {
     conn.execute(
         "UPDATE users SET is_active = 0 WHERE id = ?",
@@ -97,21 +331,10 @@ pub fn deactivate_user(conn: &Connection, user_id: i32) -> Result<()> {
 
 pub fn get_user_by_id(conn: &Connection, user_id: i32) -> Result<Option<User>> {
     let mut stmt = conn.prepare(
-        "SELECT id, username, email, password_hash, role, full_name, is_active 
-        FROM users WHERE id = ?",
+        &format!("SELECT {} FROM users WHERE id = ?", USER_COLUMNS)
     )?;
 
-    let mut rows = stmt.query_map(params![user_id], |row| {
-        Ok(User {
-            id: row.get(0)?,
-            username: row.get(1)?,
-            email: row.get(2)?,
-            password_hash: row.get(3)?,
-            role: UserRole::from(row.get::<_, String>(4)?.as_str()),
-            full_name: row.get(5)?,
-            is_active: row.get(6)?,
-        })
-    })?;
+    let mut rows = stmt.query_map(params![user_id], row_to_user)?;
 
     if let Some(user) = rows.next() {
         Ok(Some(user?))
@@ -121,20 +344,8 @@ pub fn get_user_by_id(conn: &Connection, user_id: i32) -> Result<Option<User>> {
 }
 
 pub fn get_all_users(conn: &Connection) -> Result<Vec<User>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, username, email, password_hash, role, full_name, is_active FROM users"
-    )?;
-    let users = stmt.query_map([], |row| {
-        Ok(User {
-            id: row.get(0)?,
-            username: row.get(1)?,
-            email: row.get(2)?,
-            password_hash: row.get(3)?,
-            role: UserRole::from(row.get::<_, String>(4)?.as_str()),
-            full_name: row.get(5)?,
-            is_active: row.get(6)?,
-        })
-    })?.collect();
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM users", USER_COLUMNS))?;
+    let users = stmt.query_map([], row_to_user)?.collect();
     users
 }
 
@@ -158,6 +369,8 @@ pub fn delete_user(conn: &Connection, user_id: i32) -> Result<usize, rusqlite::E
         return Err(rusqlite::Error::QueryReturnedNoRows);
     }
 
+    conn.execute("DELETE FROM password_history WHERE user_id = ?", params![user_id])?;
+
     // Eliminar el usuario
     let rows_affected = conn.execute(
         "DELETE FROM users WHERE id = ?", 
@@ -168,3 +381,70 @@ pub fn delete_user(conn: &Connection, user_id: i32) -> Result<usize, rusqlite::E
     
     Ok(rows_affected)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::models::settings::PasswordPolicy;
+
+    fn connection() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        settings_queries::create_table(&conn).unwrap();
+        create_table(&conn).unwrap();
+        conn
+    }
+
+    fn new_user(username: &str, password: &str) -> NewUser {
+        NewUser {
+            username: username.to_string(),
+            email: format!("{}@example.com", username),
+            password: password.to_string(),
+            role: UserRole::Seller,
+            full_name: username.to_string(),
+        }
+    }
+
+    #[test]
+    fn password_policy_lists_every_missing_rule() {
+        let conn = connection();
+        let error = check_password_policy(&conn, None, "ana", "abc").unwrap_err().to_string();
+        assert!(error.contains("al menos 8 caracteres"));
+        assert!(error.contains("mayúscula"));
+        assert!(error.contains("número"));
+        assert!(!error.contains("minúscula"));
+        assert!(check_password_policy(&conn, None, "ana", "Secreto123").is_ok());
+    }
+
+    #[test]
+    fn password_policy_rejects_the_username() {
+        let conn = connection();
+        let error = check_password_policy(&conn, None, "Cajero2024", "cajero2024").unwrap_err().to_string();
+        assert!(error.contains("nombre de usuario"));
+    }
+
+    #[test]
+    fn password_policy_applies_saved_settings() {
+        let conn = connection();
+        settings_queries::save_password_policy(&conn, &PasswordPolicy {
+            min_length: 4,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: true,
+            ..PasswordPolicy::default()
+        }).unwrap();
+        assert!(check_password_policy(&conn, None, "ana", "clave").unwrap_err().to_string().contains("símbolo"));
+        assert!(check_password_policy(&conn, None, "ana", "clave!").is_ok());
+    }
+
+    #[test]
+    fn password_policy_rejects_recent_passwords() {
+        let conn = connection();
+        let user_id = create_user(&conn, new_user("ana", "Secreto123")).unwrap();
+        update_user_password(&conn, user_id, "Secreto456", false).unwrap();
+
+        let error = update_user_password(&conn, user_id, "Secreto123", false).unwrap_err().to_string();
+        assert!(error.contains("últimas 5 contraseñas"));
+        // El historial es por usuario
+        assert!(check_password_policy(&conn, None, "ana", "Secreto123").is_ok());
+    }
+}
@@ -0,0 +1,414 @@
+use std::collections::{HashMap, HashSet};
+use rusqlite::{params, Connection, Result};
+use chrono::NaiveDateTime;
+use super::super::errors::validation_error;
+use super::super::models::product::NewProduct;
+use super::super::models::variant::{
+    AttributeValue, NewProductTemplate, ProductTemplate, ProductVariant, TemplateAttribute, TemplateSales,
+};
+use super::{category_queries, product_queries};
+
+pub fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS product_templates (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            base_code TEXT UNIQUE NOT NULL,
+            base_price REAL NOT NULL CHECK(base_price >= 0),
+            description TEXT,
+            category_id INTEGER REFERENCES categories(id),
+            unit TEXT NOT NULL DEFAULT 'pza',
+            quantity_decimals INTEGER NOT NULL DEFAULT 0 CHECK(quantity_decimals BETWEEN 0 AND 3)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS template_attributes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            template_id INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            position INTEGER NOT NULL,
+            UNIQUE(template_id, name),
+            FOREIGN KEY (template_id) REFERENCES product_templates(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS template_attribute_values (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            attribute_id INTEGER NOT NULL,
+            value TEXT NOT NULL,
+            position INTEGER NOT NULL,
+            UNIQUE(attribute_id, value),
+            FOREIGN KEY (attribute_id) REFERENCES template_attributes(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    // Cada variante es un producto; aquí se liga a su modelo y a sus valores
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS product_variants (
+            product_id INTEGER PRIMARY KEY,
+            template_id INTEGER NOT NULL,
+            price_override REAL CHECK(price_override >= 0),
+            FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE,
+            FOREIGN KEY (template_id) REFERENCES product_templates(id)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_product_variants_template ON product_variants(template_id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS product_variant_values (
+            product_id INTEGER NOT NULL,
+            value_id INTEGER NOT NULL,
+            PRIMARY KEY (product_id, value_id),
+            FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE,
+            FOREIGN KEY (value_id) REFERENCES template_attribute_values(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn insert_value(conn: &Connection, attribute_id: i32, value: &str) -> Result<i32> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(validation_error("Los valores de un atributo no pueden estar vacíos"));
+    }
+    conn.execute(
+        "INSERT INTO template_attribute_values (attribute_id, value, position)
+        SELECT ?1, ?2, COALESCE(MAX(position), -1) + 1 FROM template_attribute_values WHERE attribute_id = ?1",
+        params![attribute_id, value],
+    )?;
+    Ok(conn.last_insert_rowid() as i32)
+}
+
+pub fn create_template(conn: &Connection, template: &NewProductTemplate) -> Result<i32> {
+    if template.name.trim().is_empty() || template.base_code.trim().is_empty() {
+        return Err(validation_error("El modelo requiere nombre y código base"));
+    }
+    if template.attributes.is_empty() || template.attributes.iter().any(|a| a.values.is_empty()) {
+        return Err(validation_error("Cada atributo del modelo necesita al menos un valor"));
+    }
+    if template.quantity_decimals > 3 {
+        return Err(validation_error("Las cantidades admiten a lo más 3 decimales"));
+    }
+    category_queries::ensure_category(conn, template.category_id)?;
+
+    conn.execute(
+        "INSERT INTO product_templates
+        (name, base_code, base_price, description, category_id, unit, quantity_decimals)
+        VALUES (?, ?, ?, ?, ?, ?, ?)",
+        params![
+            template.name.trim(),
+            template.base_code.trim(),
+            template.base_price,
+            template.description,
+            template.category_id,
+            template.unit.trim(),
+            template.quantity_decimals
+        ],
+    )?;
+    let id = conn.last_insert_rowid() as i32;
+
+    for (position, attribute) in template.attributes.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO template_attributes (template_id, name, position) VALUES (?, ?, ?)",
+            params![id, attribute.name.trim(), position as i32],
+        )?;
+        let attribute_id = conn.last_insert_rowid() as i32;
+        for value in &attribute.values {
+            insert_value(conn, attribute_id, value)?;
+        }
+    }
+    Ok(id)
+}
+
+// Agrega un valor (p. ej. una talla nueva); las variantes se generan aparte
+pub fn add_attribute_value(conn: &Connection, attribute_id: i32, value: &str) -> Result<i32> {
+    insert_value(conn, attribute_id, value)
+}
+
+fn get_attributes(conn: &Connection, template_id: i32) -> Result<Vec<TemplateAttribute>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name FROM template_attributes WHERE template_id = ? ORDER BY position"
+    )?;
+    let attributes = stmt.query_map([template_id], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut values_stmt = conn.prepare(
+        "SELECT id, value FROM template_attribute_values WHERE attribute_id = ? ORDER BY position"
+    )?;
+    attributes
+        .into_iter()
+        .map(|(id, name)| {
+            let values = values_stmt
+                .query_map([id], |row| Ok(AttributeValue { id: row.get(0)?, value: row.get(1)? }))?
+                .collect::<Result<Vec<_>>>()?;
+            Ok(TemplateAttribute { id, name, values })
+        })
+        .collect()
+}
+
+pub fn get_template(conn: &Connection, id: i32) -> Result<ProductTemplate> {
+    let mut template = conn.query_row(
+        "SELECT t.id, t.name, t.base_code, t.base_price, t.description, t.category_id, t.unit,
+            t.quantity_decimals, COUNT(v.product_id), COALESCE(SUM(p.stock), 0)
+        FROM product_templates t
+        LEFT JOIN product_variants v ON v.template_id = t.id
+        LEFT JOIN products p ON p.id = v.product_id
+        WHERE t.id = ?
+        GROUP BY t.id",
+        [id],
+        |row| {
+            Ok(ProductTemplate {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                base_code: row.get(2)?,
+                base_price: row.get(3)?,
+                description: row.get(4)?,
+                category_id: row.get(5)?,
+                unit: row.get(6)?,
+                quantity_decimals: row.get(7)?,
+                attributes: Vec::new(),
+                variant_count: row.get(8)?,
+                total_stock: row.get(9)?,
+            })
+        },
+    )?;
+    template.attributes = get_attributes(conn, id)?;
+    Ok(template)
+}
+
+pub fn get_templates(conn: &Connection) -> Result<Vec<ProductTemplate>> {
+    let mut stmt = conn.prepare("SELECT id FROM product_templates ORDER BY name")?;
+    let ids = stmt.query_map([], |row| row.get::<_, i32>(0))?
+        .collect::<Result<Vec<_>>>()?;
+    ids.into_iter().map(|id| get_template(conn, id)).collect()
+}
+
+pub fn get_variant(conn: &Connection, product_id: i32) -> Result<ProductVariant> {
+    let (template_id, price_override) = conn.query_row(
+        "SELECT template_id, price_override FROM product_variants WHERE product_id = ?",
+        [product_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let mut stmt = conn.prepare(
+        "SELECT v.value
+        FROM product_variant_values pv
+        JOIN template_attribute_values v ON v.id = pv.value_id
+        JOIN template_attributes a ON a.id = v.attribute_id
+        WHERE pv.product_id = ?
+        ORDER BY a.position"
+    )?;
+    let values = stmt.query_map([product_id], |row| row.get(0))?
+        .collect::<Result<Vec<String>>>()?;
+
+    Ok(ProductVariant {
+        product: product_queries::get_product(conn, product_id)?,
+        template_id,
+        price_override,
+        values,
+    })
+}
+
+pub fn get_variants(conn: &Connection, template_id: i32) -> Result<Vec<ProductVariant>> {
+    let mut stmt = conn.prepare(
+        "SELECT product_id FROM product_variants WHERE template_id = ? ORDER BY product_id"
+    )?;
+    let ids = stmt.query_map([template_id], |row| row.get::<_, i32>(0))?
+        .collect::<Result<Vec<_>>>()?;
+    ids.into_iter().map(|id| get_variant(conn, id)).collect()
+}
+
+// Fragmento del código de la variante: "Azul marino" → "AZULMARINO"
+fn code_fragment(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+// Crea un producto por cada combinación de valores que todavía no tenga
+// variante. Regenerar después de agregar una talla solo crea las nuevas.
+pub fn generate_variants(conn: &Connection, user_id: i32, template_id: i32) -> Result<Vec<i32>> {
+    let template = get_template(conn, template_id)?;
+
+    let mut combinations: Vec<Vec<&AttributeValue>> = vec![Vec::new()];
+    for attribute in &template.attributes {
+        combinations = combinations
+            .into_iter()
+            .flat_map(|combination| {
+                attribute.values.iter().map(move |value| {
+                    let mut next = combination.clone();
+                    next.push(value);
+                    next
+                })
+            })
+            .collect();
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT pv.product_id, pv.value_id
+        FROM product_variant_values pv
+        JOIN product_variants v ON v.product_id = pv.product_id
+        WHERE v.template_id = ?"
+    )?;
+    let mut by_product: HashMap<i32, Vec<i32>> = HashMap::new();
+    for pair in stmt.query_map([template_id], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, i32>(1)?)))? {
+        let (product_id, value_id) = pair?;
+        by_product.entry(product_id).or_default().push(value_id);
+    }
+    let existing = by_product
+        .into_values()
+        .map(|mut value_ids| {
+            value_ids.sort();
+            value_ids
+        })
+        .collect::<HashSet<_>>();
+
+    let mut created = Vec::new();
+    for combination in combinations {
+        let mut value_ids = combination.iter().map(|value| value.id).collect::<Vec<_>>();
+        value_ids.sort();
+        if existing.contains(&value_ids) {
+            continue;
+        }
+
+        let labels = combination.iter().map(|value| value.value.as_str()).collect::<Vec<_>>();
+        let code = std::iter::once(template.base_code.clone())
+            .chain(labels.iter().map(|label| code_fragment(label)))
+            .collect::<Vec<_>>()
+            .join("-");
+        let product_id = product_queries::create_product(conn, user_id, NewProduct {
+            name: format!("{} {}", template.name, labels.join(" / ")),
+            code,
+            price: template.base_price,
+            stock: 0.0,
+            allow_backorder: false,
+            description: template.description.clone(),
+            plu: None,
+            unit: template.unit.clone(),
+            quantity_decimals: template.quantity_decimals,
+            category_id: template.category_id,
+        })?;
+
+        conn.execute(
+            "INSERT INTO product_variants (product_id, template_id) VALUES (?, ?)",
+            params![product_id, template_id],
+        )?;
+        for value_id in value_ids {
+            conn.execute(
+                "INSERT INTO product_variant_values (product_id, value_id) VALUES (?, ?)",
+                params![product_id, value_id],
+            )?;
+        }
+        created.push(product_id);
+    }
+    Ok(created)
+}
+
+// El precio de venta vive en `products.price`; sin excepción se usa el base
+pub fn set_variant_price(conn: &Connection, product_id: i32, price_override: Option<f64>) -> Result<()> {
+    if price_override.is_some_and(|price| price < 0.0) {
+        return Err(validation_error("El precio no puede ser negativo"));
+    }
+    let updated = conn.execute(
+        "UPDATE product_variants SET price_override = ? WHERE product_id = ?",
+        params![price_override, product_id],
+    )?;
+    if updated == 0 {
+        return Err(validation_error(format!("El producto {} no es una variante", product_id)));
+    }
+    conn.execute(
+        "UPDATE products SET price = COALESCE(
+            (SELECT v.price_override FROM product_variants v WHERE v.product_id = products.id),
+            (SELECT t.base_price FROM product_variants v JOIN product_templates t ON t.id = v.template_id
+             WHERE v.product_id = products.id)
+        )
+        WHERE id = ?",
+        [product_id],
+    )?;
+    Ok(())
+}
+
+// Un precio editado desde el producto se vuelve el precio propio de la
+// variante; si no, el siguiente cambio del precio base lo sobrescribiría
+pub fn record_price_override(conn: &Connection, product_id: i32, price: f64) -> Result<()> {
+    conn.execute(
+        "UPDATE product_variants SET price_override = ? WHERE product_id = ?",
+        params![price, product_id],
+    )?;
+    Ok(())
+}
+
+// Sin llaves foráneas activas las cascadas no se disparan; al borrar el
+// producto se borran aquí sus ligas con el modelo
+pub fn delete_variant_links(conn: &Connection, product_id: i32) -> Result<()> {
+    conn.execute("DELETE FROM product_variant_values WHERE product_id = ?", [product_id])?;
+    conn.execute("DELETE FROM product_variants WHERE product_id = ?", [product_id])?;
+    Ok(())
+}
+
+// Cambia el precio base y lo propaga a las variantes sin precio propio
+pub fn set_template_price(conn: &Connection, template_id: i32, base_price: f64) -> Result<()> {
+    if base_price < 0.0 {
+        return Err(validation_error("El precio no puede ser negativo"));
+    }
+    conn.execute(
+        "UPDATE product_templates SET base_price = ? WHERE id = ?",
+        params![base_price, template_id],
+    )?;
+    conn.execute(
+        "UPDATE products SET price = ?1
+        WHERE id IN (SELECT product_id FROM product_variants WHERE template_id = ?2 AND price_override IS NULL)",
+        params![base_price, template_id],
+    )?;
+    Ok(())
+}
+
+// Ventas completadas del periodo sumando todas las variantes de cada modelo,
+// netas de lo devuelto en cada línea como en el reporte por categoría
+pub fn get_sales_by_template(conn: &Connection, start: NaiveDateTime, end: NaiveDateTime) -> Result<Vec<TemplateSales>> {
+    let start_str = start.format("%Y-%m-%d %H:%M:%S").to_string();
+    let end_str = end.format("%Y-%m-%d %H:%M:%S").to_string();
+
+    let mut stmt = conn.prepare(
+        "WITH lines AS (
+            SELECT v.template_id, sd.sale_id,
+                sd.quantity - COALESCE(r.quantity, 0) AS quantity,
+                sd.subtotal - COALESCE(r.subtotal, 0) AS subtotal,
+                sd.tax_amount - COALESCE(r.tax_amount, 0) AS tax_amount
+            FROM sale_details sd
+            JOIN sales s ON s.id = sd.sale_id
+            JOIN product_variants v ON v.product_id = sd.product_id
+            LEFT JOIN (
+                SELECT sale_detail_id, SUM(quantity) AS quantity, SUM(subtotal) AS subtotal,
+                    SUM(tax_amount) AS tax_amount
+                FROM return_items GROUP BY sale_detail_id
+            ) r ON r.sale_detail_id = sd.id
+            WHERE s.status IN ('completed', 'refunded') AND s.date BETWEEN ? AND ?
+        )
+        SELECT t.id, t.name, COUNT(DISTINCT CASE WHEN l.quantity > 0 THEN l.sale_id END),
+            SUM(l.quantity), SUM(l.subtotal), SUM(l.tax_amount)
+        FROM lines l
+        JOIN product_templates t ON t.id = l.template_id
+        GROUP BY t.id
+        ORDER BY SUM(l.subtotal) DESC"
+    )?;
+    let totals = stmt.query_map(params![start_str, end_str], |row| {
+        Ok(TemplateSales {
+            template_id: row.get(0)?,
+            name: row.get(1)?,
+            sales_count: row.get(2)?,
+            quantity: row.get(3)?,
+            net_sales: row.get(4)?,
+            taxes: row.get(5)?,
+        })
+    })?
+    .collect::<Result<Vec<_>>>()?;
+    Ok(totals)
+}
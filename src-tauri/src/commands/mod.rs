@@ -1,2 +1,3 @@
 pub mod commands; 
-pub mod database;
\ No newline at end of file
+pub mod database;
+pub mod printing;
\ No newline at end of file
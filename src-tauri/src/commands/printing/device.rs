@@ -0,0 +1,89 @@
+use std::fs::OpenOptions;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use serde::Serialize;
+use crate::commands::database::models::settings::PrinterConnection;
+use super::escpos;
+
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn connect(host: &str, port: u16) -> io::Result<TcpStream> {
+    let address = (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No se encontró la impresora en la red"))?;
+    let stream = TcpStream::connect_timeout(&address, NETWORK_TIMEOUT)?;
+    stream.set_read_timeout(Some(NETWORK_TIMEOUT))?;
+    stream.set_write_timeout(Some(NETWORK_TIMEOUT))?;
+    Ok(stream)
+}
+
+// Envía el flujo tal cual: al dispositivo del sistema, a un archivo (útil
+// para pruebas o colas de impresión) o a un socket RAW, normalmente el 9100.
+pub fn send(connection: &PrinterConnection, bytes: &[u8]) -> io::Result<()> {
+    match connection {
+        PrinterConnection::Device { path } => {
+            let mut device = OpenOptions::new().write(true).open(path)?;
+            device.write_all(bytes)?;
+            device.flush()
+        }
+        PrinterConnection::File { path } => {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            file.write_all(bytes)
+        }
+        PrinterConnection::Network { host, port } => {
+            let mut stream = connect(host, *port)?;
+            stream.write_all(bytes)?;
+            stream.flush()
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PrinterStatus {
+    pub online: bool,
+    pub cover_open: bool,
+    pub paper_near_end: bool,
+    pub paper_out: bool,
+    pub error: bool,
+}
+
+fn request<T: Read + Write>(port: &mut T, n: u8) -> io::Result<u8> {
+    port.write_all(&escpos::status_request(n))?;
+    port.flush()?;
+    let mut response = [0u8; 1];
+    port.read_exact(&mut response)?;
+    Ok(response[0])
+}
+
+fn read_status<T: Read + Write>(port: &mut T) -> io::Result<PrinterStatus> {
+    let printer = request(port, 1)?;
+    let offline = request(port, 2)?;
+    let paper = request(port, 4)?;
+    Ok(PrinterStatus {
+        online: printer & 0x08 == 0,
+        cover_open: offline & 0x04 != 0,
+        paper_near_end: paper & 0x0c != 0,
+        paper_out: paper & 0x60 != 0,
+        error: offline & 0x40 != 0,
+    })
+}
+
+// Solo las impresoras con canal de regreso (dispositivo o red) reportan su estado
+pub fn query_status(connection: &PrinterConnection) -> io::Result<PrinterStatus> {
+    match connection {
+        PrinterConnection::Device { path } => {
+            let mut device = OpenOptions::new().read(true).write(true).open(path)?;
+            read_status(&mut device)
+        }
+        PrinterConnection::Network { host, port } => {
+            let mut stream = connect(host, *port)?;
+            read_status(&mut stream)
+        }
+        PrinterConnection::File { .. } => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "La impresora configurada como archivo no reporta su estado",
+        )),
+    }
+}
@@ -0,0 +1,156 @@
+// Constructor de flujos ESC/POS. El texto se codifica en la página de
+// códigos 850, que incluye los acentos y la ñ.
+const ESC: u8 = 0x1b;
+const GS: u8 = 0x1d;
+const DLE: u8 = 0x10;
+const EOT: u8 = 0x04;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Alignment {
+    Left,
+    Center,
+}
+
+pub struct EscPos {
+    buffer: Vec<u8>,
+    columns: usize,
+}
+
+impl EscPos {
+    pub fn new(columns: usize) -> Self {
+        let mut escpos = EscPos {
+            buffer: Vec::new(),
+            columns,
+        };
+        escpos.raw(&[ESC, b'@']);
+        // ESC t 2: página de códigos PC850
+        escpos.raw(&[ESC, b't', 2]);
+        escpos
+    }
+
+    pub fn raw(&mut self, bytes: &[u8]) -> &mut Self {
+        self.buffer.extend_from_slice(bytes);
+        self
+    }
+
+    pub fn align(&mut self, alignment: Alignment) -> &mut Self {
+        let n = match alignment {
+            Alignment::Left => 0,
+            Alignment::Center => 1,
+        };
+        self.raw(&[ESC, b'a', n])
+    }
+
+    pub fn bold(&mut self, enabled: bool) -> &mut Self {
+        self.raw(&[ESC, b'E', enabled as u8])
+    }
+
+    pub fn double_size(&mut self, enabled: bool) -> &mut Self {
+        self.raw(&[GS, b'!', if enabled { 0x11 } else { 0x00 }])
+    }
+
+    pub fn text(&mut self, text: &str) -> &mut Self {
+        let encoded = encode_cp850(text);
+        self.raw(&encoded)
+    }
+
+    pub fn line(&mut self, text: &str) -> &mut Self {
+        self.text(text).raw(b"\n")
+    }
+
+    pub fn separator(&mut self) -> &mut Self {
+        let line = "-".repeat(self.columns);
+        self.line(&line)
+    }
+
+    // Texto a la izquierda e importe alineado a la derecha en el mismo renglón
+    pub fn row(&mut self, left: &str, right: &str) -> &mut Self {
+        let right_width = right.chars().count();
+        let left_width = self.columns.saturating_sub(right_width + 1);
+        let left: String = left.chars().take(left_width).collect();
+        let padding = self.columns - left.chars().count() - right_width;
+        let line = format!("{}{}{}", left, " ".repeat(padding), right);
+        self.line(&line)
+    }
+
+    pub fn feed(&mut self, lines: u8) -> &mut Self {
+        self.raw(&[ESC, b'd', lines])
+    }
+
+    // Corte parcial dejando avanzar el papel hasta la cuchilla
+    pub fn cut(&mut self) -> &mut Self {
+        self.raw(&[GS, b'V', 66, 0])
+    }
+
+    pub fn barcode_code128(&mut self, data: &str) -> &mut Self {
+        let data: Vec<u8> = data.bytes().filter(|b| b.is_ascii_graphic()).collect();
+        self.raw(&[GS, b'h', 80]);
+        self.raw(&[GS, b'w', 2]);
+        // Texto legible debajo de las barras
+        self.raw(&[GS, b'H', 2]);
+        self.raw(&[GS, b'k', 73, (data.len() + 2) as u8, b'{', b'B']);
+        self.raw(&data);
+        self.raw(b"\n")
+    }
+
+    pub fn qr(&mut self, data: &str) -> &mut Self {
+        let bytes = data.as_bytes();
+        let length = bytes.len() + 3;
+        let (pl, ph) = ((length % 256) as u8, (length / 256) as u8);
+        // Modelo 2, módulo de 6 puntos, corrección de errores M
+        self.raw(&[GS, b'(', b'k', 4, 0, 49, 65, 50, 0]);
+        self.raw(&[GS, b'(', b'k', 3, 0, 49, 67, 6]);
+        self.raw(&[GS, b'(', b'k', 3, 0, 49, 69, 49]);
+        self.raw(&[GS, b'(', b'k', pl, ph, 49, 80, 48]);
+        self.raw(bytes);
+        self.raw(&[GS, b'(', b'k', 3, 0, 49, 81, 48]);
+        self.raw(b"\n")
+    }
+
+    // Pulso al conector del cajón (pin 2): 50 ms encendido, 500 ms apagado
+    pub fn drawer_kick(&mut self) -> &mut Self {
+        self.raw(&[ESC, b'p', 0, 25, 250])
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buffer
+    }
+}
+
+// DLE EOT n: estado en tiempo real. 1 = impresora, 2 = fuera de línea,
+// 4 = sensor de papel. La impresora responde con un byte.
+pub fn status_request(n: u8) -> [u8; 3] {
+    [DLE, EOT, n]
+}
+
+pub fn drawer_kick() -> Vec<u8> {
+    let mut escpos = EscPos::new(0);
+    escpos.drawer_kick();
+    escpos.into_bytes()
+}
+
+fn encode_cp850(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            c if c.is_ascii() => c as u8,
+            'ü' => 0x81,
+            'é' => 0x82,
+            'á' => 0xa0,
+            'í' => 0xa1,
+            'ó' => 0xa2,
+            'ú' => 0xa3,
+            'ñ' => 0xa4,
+            'Ñ' => 0xa5,
+            '¿' => 0xa8,
+            '¡' => 0xad,
+            'Á' => 0xb5,
+            'É' => 0x90,
+            'Í' => 0xd6,
+            'Ó' => 0xe0,
+            'Ú' => 0xe9,
+            'Ü' => 0x9a,
+            '°' => 0xf8,
+            _ => b'?',
+        })
+        .collect()
+}
@@ -0,0 +1,3 @@
+pub mod escpos;
+pub mod receipt;
+pub mod device;
\ No newline at end of file
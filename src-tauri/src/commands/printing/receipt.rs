@@ -0,0 +1,100 @@
+use rusqlite::{Connection, Result};
+use crate::commands::database::errors::validation_error;
+use crate::commands::database::models::sale::SaleStatus;
+use crate::commands::database::queries::{
+    customer_queries, payment_queries, sale_detail_queries, sale_queries, settings_queries,
+};
+use super::escpos::{Alignment, EscPos};
+
+fn money(amount: f64) -> String {
+    format!("${:.2}", amount)
+}
+
+// Ticket de venta listo para enviarse a la impresora. Las reimpresiones
+// llevan la leyenda COPIA en el encabezado.
+pub fn render_sale_receipt(conn: &Connection, sale_id: i32, is_copy: bool) -> Result<Vec<u8>> {
+    let sale = sale_queries::get_sale(conn, sale_id)?;
+    if SaleStatus::from(sale.status.as_str()) == SaleStatus::Draft {
+        return Err(validation_error("La venta aún no se ha cobrado"));
+    }
+
+    let business = settings_queries::get_business_settings(conn)?;
+    let printer = settings_queries::get_printer_settings(conn)?;
+    let details = sale_detail_queries::get_details_with_products_by_sale(conn, sale_id)?;
+    let payments = payment_queries::get_payments_by_sale(conn, sale_id)?;
+    let folio = format!("{:08}", sale.id);
+
+    let mut ticket = EscPos::new(printer.paper_width.columns());
+
+    ticket.align(Alignment::Center).bold(true).double_size(true);
+    ticket.line(&business.business_name);
+    ticket.double_size(false).bold(false);
+    if let Some(rfc) = &business.rfc {
+        ticket.line(&format!("RFC: {}", rfc));
+    }
+    if let Some(address) = &business.address {
+        ticket.line(address);
+    }
+    if let Some(phone) = &business.phone {
+        ticket.line(&format!("Tel. {}", phone));
+    }
+    if is_copy {
+        ticket.bold(true).double_size(true).line("COPIA").double_size(false).bold(false);
+    }
+    if SaleStatus::from(sale.status.as_str()) == SaleStatus::Canceled {
+        ticket.bold(true).line("VENTA CANCELADA").bold(false);
+    }
+
+    ticket.align(Alignment::Left).separator();
+    ticket.row(&format!("Folio: {}", folio), &sale.date.format("%d/%m/%Y %H:%M").to_string());
+    if let Some(customer_id) = sale.customer_id {
+        let customer = customer_queries::get_customer(conn, customer_id)?;
+        ticket.line(&format!("Cliente: {}", customer.name));
+    }
+    ticket.separator();
+
+    for detail in &details {
+        ticket.line(&detail.product_name);
+        ticket.row(
+            &format!("  {} {} x {}", detail.quantity, detail.unit, money(detail.unit_price)),
+            &money(detail.unit_price * detail.quantity),
+        );
+        if detail.discount > 0.0 {
+            ticket.row("  Descuento", &format!("-{}", money(detail.discount)));
+        }
+    }
+
+    ticket.separator();
+    ticket.row("Subtotal", &money(sale.subtotal));
+    ticket.row("Impuestos", &money(sale.taxes));
+    ticket.bold(true).row("TOTAL", &money(sale.total)).bold(false);
+    ticket.separator();
+
+    let mut change_due = 0.0;
+    for payment in &payments {
+        let label = match &payment.card_last4 {
+            Some(last4) => format!("{} ****{}", payment.method.label(), last4),
+            None => payment.method.label().to_string(),
+        };
+        ticket.row(&label, &money(payment.amount));
+        change_due += payment.change_amount;
+    }
+    if change_due > 0.0 {
+        ticket.row("Cambio", &money(change_due));
+    }
+
+    ticket.align(Alignment::Center);
+    if let Some(footer) = &printer.footer {
+        ticket.feed(1).line(footer);
+    }
+    if printer.print_barcode {
+        ticket.feed(1).barcode_code128(&folio);
+    }
+    if let Some(url) = &printer.self_invoice_url {
+        ticket.feed(1).line("Facture su compra en línea:");
+        ticket.qr(&format!("{}?folio={}&total={:.2}", url, folio, sale.total));
+    }
+
+    ticket.feed(3).cut();
+    Ok(ticket.into_bytes())
+}
@@ -27,11 +27,45 @@ fn main() {
             db::get_all_products,
             db::update_product,
             db::delete_product,
+            db::find_product_by_code,
+            db::search_products,
+            db::search_products_text,
+            db::add_product_barcode,
+            db::get_product_barcodes,
+            db::delete_product_barcode,
+            db::add_product_unit,
+            db::get_product_units,
+            db::delete_product_unit,
+            
+            /* ========== CATEGORÍAS ========== */
+            db::add_category,
+            db::get_categories,
+            db::update_category,
+            db::delete_category,
+            
+            /* ========== MODELOS Y VARIANTES ========== */
+            db::add_product_template,
+            db::get_product_templates,
+            db::add_template_attribute_value,
+            db::update_template_price,
+            db::generate_product_variants,
+            db::get_product_variants,
+            db::set_variant_price,
+            
+            /* ========== PAQUETES ========== */
+            db::set_bundle_components,
+            db::get_bundle,
+            db::get_bundles,
+            
+            /* ========== INVENTARIO ========== */
+            db::record_inventory_movement,
+            db::get_product_kardex,
             
             /* ========== CLIENTES ========== */
             db::add_customer,
             db::get_customer,
             db::get_all_customers,
+            db::search_customers,
             db::update_customer,
             db::delete_customer,
             
@@ -40,13 +74,45 @@ fn main() {
             db::get_sale,
             db::get_sales_by_date_range,
             db::cancel_sale,
+            db::complete_sale,
+            db::get_sale_payments,
+            db::get_tender_report,
+            db::get_category_sales_report,
+            db::get_template_sales_report,
+            db::verify_sale_chain,
+            
+            /* ========== CAJAS Y TURNOS ========== */
+            db::create_cash_register,
+            db::get_cash_registers,
+            db::open_register_session,
+            db::get_current_register_session,
+            db::get_register_session,
+            db::close_register_session,
+            db::record_cash_movement,
+            db::get_cash_movements,
+            
+            /* ========== CORTES X Y Z ========== */
+            db::get_x_report,
+            db::generate_z_report,
+            db::get_z_report,
+            db::get_z_reports,
+            
+            /* ========== IMPRESIÓN ========== */
+            db::print_receipt,
+            db::get_printer_status,
+            db::open_cash_drawer,
             
             /* ========== DETALLES DE VENTA ========== */
             db::add_sale_detail,
+            db::add_scanned_sale_detail,
             db::get_sale_details,
             db::remove_sale_detail,
             db::get_sale_details_basic,
             
+            /* ========== DEVOLUCIONES ========== */
+            db::create_return,
+            db::get_returns_by_sale,
+            
             /* ========== FACTURAS ========== */
             db::create_invoice,
             db::get_invoice_by_uuid,
@@ -57,11 +123,50 @@ fn main() {
             db::create_user,
             db::authenticate_user,
             db::update_user_password,
+            db::change_own_password,
+            db::force_password_change,
             db::deactivate_user,
             db::get_user_by_id,
             db::get_all_users,
             db::update_user,
             db::delete_user,
+            db::set_user_pin,
+
+            /* ========== AUTORIZACIONES DE SUPERVISOR ========== */
+            db::request_supervisor_approval,
+
+            /* ========== VERIFICACIÓN EN DOS PASOS ========== */
+            db::verify_two_factor,
+            db::get_two_factor_status,
+            db::begin_two_factor_enrollment,
+            db::confirm_two_factor_enrollment,
+            db::regenerate_recovery_codes,
+            db::disable_two_factor,
+
+            /* ========== AUDITORÍA ========== */
+            db::get_audit_log,
+
+            /* ========== CONFIGURACIÓN INICIAL ========== */
+            db::get_setup_status,
+            db::setup_system,
+
+            /* ========== AJUSTES ========== */
+            db::get_business_settings,
+            db::update_business_settings,
+            db::get_tax_settings,
+            db::update_tax_settings,
+            db::get_password_policy,
+            db::update_password_policy,
+            db::get_two_factor_policy,
+            db::update_two_factor_policy,
+            db::get_pos_restrictions,
+            db::update_pos_restrictions,
+            db::get_inventory_settings,
+            db::update_inventory_settings,
+            db::get_printer_settings,
+            db::update_printer_settings,
+            db::get_scale_barcode_settings,
+            db::update_scale_barcode_settings,
         ])
         .run(tauri::generate_context!())
         .expect("Error al ejecutar la aplicación Tauri");
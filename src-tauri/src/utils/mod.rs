@@ -1 +1,2 @@
-pub mod date_format;
\ No newline at end of file
+pub mod date_format;
+pub mod totp;
\ No newline at end of file
@@ -0,0 +1,125 @@
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+
+// Parámetros estándar de RFC 6238, compatibles con Google Authenticator,
+// Authy y similares.
+const STEP_SECONDS: u64 = 30;
+const DIGITS: u32 = 6;
+const SECRET_BYTES: usize = 20;
+const ALPHABET: base32::Alphabet = base32::Alphabet::Rfc4648 { padding: false };
+
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; SECRET_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    base32::encode(ALPHABET, &bytes)
+}
+
+pub fn current_step(unix_time: u64) -> u64 {
+    unix_time / STEP_SECONDS
+}
+
+fn code_for_step(secret: &[u8], step: u64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC acepta claves de cualquier tamaño");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    // Truncamiento dinámico (RFC 4226, sección 5.3)
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = ((digest[offset] as u32 & 0x7f) << 24)
+        | ((digest[offset + 1] as u32) << 16)
+        | ((digest[offset + 2] as u32) << 8)
+        | (digest[offset + 3] as u32);
+
+    format!("{:0width$}", binary % 10u32.pow(DIGITS), width = DIGITS as usize)
+}
+
+// Devuelve el paso de tiempo que coincidió con el código, aceptando un paso
+// de desfase en cada dirección por diferencias de reloj.
+pub fn verify_code(secret: &str, code: &str, unix_time: u64) -> Option<u64> {
+    let secret = base32::decode(ALPHABET, secret)?;
+    let code = code.trim().replace(' ', "");
+    let step = current_step(unix_time);
+
+    [step.saturating_sub(1), step, step + 1]
+        .into_iter()
+        .find(|candidate| code_for_step(&secret, *candidate) == code)
+}
+
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(issuer),
+        percent_encode(account),
+        secret,
+        percent_encode(issuer),
+        DIGITS,
+        STEP_SECONDS
+    )
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Semilla SHA-1 del apéndice B de RFC 6238; los vectores son de 8 dígitos
+    // y aquí se comparan sus últimos 6
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+    const RFC_VECTORS: [(u64, &str); 6] = [
+        (59, "287082"),
+        (1111111109, "081804"),
+        (1111111111, "050471"),
+        (1234567890, "005924"),
+        (2000000000, "279037"),
+        (20000000000, "353130"),
+    ];
+
+    #[test]
+    fn codes_match_rfc_6238_vectors() {
+        for (unix_time, expected) in RFC_VECTORS {
+            assert_eq!(code_for_step(RFC_SECRET, current_step(unix_time)), expected, "T = {}", unix_time);
+        }
+    }
+
+    #[test]
+    fn verify_accepts_one_step_of_drift() {
+        let secret = base32::encode(ALPHABET, RFC_SECRET);
+        let step = current_step(1111111111);
+
+        assert_eq!(verify_code(&secret, "050 471", 1111111111), Some(step));
+        assert_eq!(verify_code(&secret, "050471", 1111111111 + STEP_SECONDS), Some(step));
+        assert_eq!(verify_code(&secret, "050471", 1111111111 - STEP_SECONDS), Some(step));
+        assert_eq!(verify_code(&secret, "050471", 1111111111 + 2 * STEP_SECONDS), None);
+        assert_eq!(verify_code(&secret, "123456", 1111111111), None);
+    }
+
+    #[test]
+    fn verify_rejects_invalid_secrets() {
+        assert_eq!(verify_code("no es base32!", "050471", 1111111111), None);
+    }
+
+    #[test]
+    fn generated_secrets_decode_to_twenty_bytes() {
+        let secret = generate_secret();
+        assert_eq!(base32::decode(ALPHABET, &secret).map(|bytes| bytes.len()), Some(SECRET_BYTES));
+    }
+
+    #[test]
+    fn otpauth_uri_escapes_labels() {
+        let uri = otpauth_uri("Mi Tienda", "ana@example.com", "ABC");
+        assert_eq!(
+            uri,
+            "otpauth://totp/Mi%20Tienda:ana%40example.com?secret=ABC&issuer=Mi%20Tienda&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+}